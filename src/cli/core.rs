@@ -1,10 +1,14 @@
-pub const CORE_COMMANDS: &[&str] = &["init", "fetch", "new", "edit", "validate", "list", "config"];
+pub const CORE_COMMANDS: &[&str] = &[
+    "init", "fetch", "new", "edit", "validate", "list", "config", "history", "logs",
+];
 
 pub mod base_command;
 pub mod config_command;
 pub mod edit_command;
 pub mod fetch_command;
+pub mod history_command;
 pub mod init_command;
 pub mod list_command;
+pub mod logs_command;
 pub mod new_command;
 pub mod validate_command;
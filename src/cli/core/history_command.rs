@@ -0,0 +1,190 @@
+use colored::Colorize;
+use indoc::printdoc;
+
+use crate::{
+    EXECUTABLE,
+    cli::core::base_command::BaseCommand,
+    runner::record::{RunStatus, RunSummary, read_runs},
+    utils::{
+        duration::format_duration,
+        fs::{get_project_folder, get_runs_folder},
+    },
+};
+use std::{error::Error, time::Duration};
+
+const STATUSES: [RunStatus; 4] = [
+    RunStatus::Running,
+    RunStatus::Success,
+    RunStatus::Failure,
+    RunStatus::Interrupted,
+];
+
+#[allow(dead_code)]
+pub struct HistoryCommand {
+    pub base: BaseCommand,
+}
+
+impl Default for HistoryCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistoryCommand {
+    pub const fn new() -> Self {
+        HistoryCommand {
+            base: BaseCommand {
+                name: "mici history",
+                description: "Lists past runs of commands, most recent first.",
+                synopsis: "mici history [<command>...] [options]",
+                options: "
+    <command>...        (argument)
+    Only list runs of this command, or of the commands below this directory.
+
+    -s, --status <status>   (option)
+    Only list runs that ended with this status: running, success, failure
+    or interrupted.
+                ",
+                usage: "
+    mici history                     # Lists all runs
+    mici history project deploy      # Lists runs of `.../project/deploy.yml`
+    mici history --status failure    # Lists failed runs
+                ",
+            },
+        }
+    }
+
+    fn display_runs(&self, runs: &[&RunSummary]) {
+        let rows: Vec<[String; 6]> = runs
+            .iter()
+            .map(|run| {
+                [
+                    run.id.clone(),
+                    run.command.clone(),
+                    run.user.clone().unwrap_or_else(|| "-".to_string()),
+                    run.started_at.clone(),
+                    run.duration_ms
+                        .map(|ms| format_duration(Duration::from_millis(ms)))
+                        .unwrap_or_else(|| "-".to_string()),
+                    run.status.to_string(),
+                ]
+            })
+            .collect();
+
+        let header = ["RUN", "COMMAND", "USER", "STARTED", "DURATION", "STATUS"];
+        let widths: Vec<usize> = (0..header.len())
+            .map(|column| {
+                rows.iter()
+                    .map(|row| row[column].len())
+                    .chain([header[column].len()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let pad = |text: &str, column: usize| format!("{:<width$}", text, width = widths[column]);
+
+        println!(
+            "  {}",
+            header
+                .iter()
+                .enumerate()
+                .map(|(column, title)| pad(title, column))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .bright_black()
+        );
+
+        for (run, row) in runs.iter().zip(&rows) {
+            let status = match run.status {
+                RunStatus::Success => row[5].green(),
+                RunStatus::Failure => row[5].red(),
+                RunStatus::Interrupted => row[5].yellow(),
+                RunStatus::Running => row[5].cyan(),
+            };
+            println!(
+                "  {}  {}  {}  {}  {}  {}",
+                pad(&row[0], 0),
+                pad(&row[1], 1).bold(),
+                pad(&row[2], 2),
+                pad(&row[3], 3),
+                pad(&row[4], 4),
+                status
+            );
+        }
+    }
+
+    pub fn run(
+        &self,
+        command_args: Vec<String>,
+        status: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let project_folder = get_project_folder();
+
+        if !project_folder.exists() {
+            printdoc! {"
+                    {} Can't list runs.
+
+                      I don't see any existing configuration at {}
+                      Try running {} {}
+                ",
+                ">".bright_black(),
+                project_folder.display().to_string().underline().bold(),
+                EXECUTABLE.get().unwrap(),
+                "init".bright_yellow().bold(),
+            };
+            return Ok(());
+        }
+
+        let status = match status {
+            Some(status) => Some(
+                STATUSES
+                    .into_iter()
+                    .find(|s| s.to_string() == status)
+                    .ok_or_else(|| {
+                        format!(
+                            "Unknown status '{}', expected one of: running, success, failure, interrupted",
+                            status
+                        )
+                    })?,
+            ),
+            None => None,
+        };
+
+        // Command paths are stored with `/`, whatever the platform
+        let command_filter = command_args.join("/");
+
+        let runs = read_runs();
+        let runs: Vec<&RunSummary> = runs
+            .iter()
+            .map(|(_, run)| run)
+            .filter(|run| {
+                command_filter.is_empty()
+                    || run.command == command_filter
+                    || run.command.starts_with(&format!("{}/", command_filter))
+            })
+            .filter(|run| status.is_none_or(|status| run.status == status))
+            .collect();
+
+        if runs.is_empty() {
+            println!("{} No runs found", ">".bright_black());
+            return Ok(());
+        }
+
+        printdoc! {"
+            {} Found {} runs in {}
+        ",
+            ">".bright_black(),
+            runs.len().to_string().bright_cyan(),
+            get_runs_folder().display().to_string().bright_yellow().bold()
+        }
+        println!();
+
+        self.display_runs(&runs);
+        println!();
+
+        Ok(())
+    }
+}
+
+pub const HISTORY_COMMAND: HistoryCommand = HistoryCommand::new();
@@ -0,0 +1,256 @@
+use colored::Colorize;
+use indoc::printdoc;
+
+use crate::{
+    EXECUTABLE,
+    cli::core::base_command::BaseCommand,
+    runner::record::{RunStatus, RunSummary, STEPS_FOLDER, read_run, read_runs, step_log_files},
+    utils::fs::get_project_folder,
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// How often `--follow` checks for new output.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The log files of a step in a run.
+struct StepLogFiles {
+    step_id: String,
+    stdout: PathBuf,
+    stderr: PathBuf,
+}
+
+#[allow(dead_code)]
+pub struct LogsCommand {
+    pub base: BaseCommand,
+}
+
+impl Default for LogsCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogsCommand {
+    pub const fn new() -> Self {
+        LogsCommand {
+            base: BaseCommand {
+                name: "mici logs",
+                description: "Prints the output of the steps of a past or running run.",
+                synopsis: "mici logs <run-id> [options]",
+                options: "
+    <run-id>            (argument)
+    Id of the run, as listed by `mici history`.
+
+    --step <id>         (option)
+    Only print the output of this step, without headers. Its stdout and
+    stderr are printed to stdout and stderr.
+
+    -f, --follow        (flag)
+    Keep printing new output until the run is over, or its mici process
+    is gone without finishing it.
+                ",
+                usage: "
+    mici logs 20261018-041231-4242                  # Prints the output of all steps
+    mici logs 20261018-041231-4242 --step build     # Prints the output of `build`
+    mici logs 20261018-041231-4242 --follow         # Follows a running command
+                ",
+            },
+        }
+    }
+
+    /// The log files to print, in the order their steps finished. Steps that are still
+    /// running come last, least recently written to first.
+    fn log_files(
+        &self,
+        directory: &Path,
+        summary: &RunSummary,
+        step: Option<&str>,
+    ) -> Vec<StepLogFiles> {
+        let files = |step_id: &str| {
+            let (stdout, stderr) = step_log_files(directory, step_id);
+            StepLogFiles {
+                step_id: step_id.to_string(),
+                stdout,
+                stderr,
+            }
+        };
+
+        if let Some(step) = step {
+            return vec![files(step)];
+        }
+
+        let mut logs: Vec<StepLogFiles> = Vec::new();
+        for step in &summary.steps {
+            if !logs.iter().any(|logs| logs.step_id == step.id) {
+                logs.push(files(&step.id));
+            }
+        }
+
+        let mut running: Vec<(SystemTime, String)> = fs::read_dir(directory.join(STEPS_FOLDER))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| !logs.iter().any(|logs| logs.stdout == entry.path()))
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let step_id = name.strip_suffix(".stdout.log")?.to_string();
+                let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+                Some((modified, step_id))
+            })
+            .collect();
+        running.sort();
+        logs.extend(running.into_iter().map(|(_, step_id)| files(&step_id)));
+
+        logs
+    }
+
+    /// Print what was added to the log files since `offsets`, returning whether there was
+    /// anything. With `headers`, output is preceded by the step it's from.
+    fn print_new_output(
+        &self,
+        logs: &[StepLogFiles],
+        offsets: &mut HashMap<PathBuf, u64>,
+        last_step: &mut Option<String>,
+        headers: bool,
+    ) -> io::Result<bool> {
+        let mut printed = false;
+
+        for step in logs {
+            for (path, is_stderr) in [(&step.stdout, false), (&step.stderr, true)] {
+                let offset = offsets.entry(path.clone()).or_default();
+                let Ok(mut file) = fs::File::open(path) else {
+                    continue;
+                };
+                file.seek(SeekFrom::Start(*offset))?;
+                let mut output = Vec::new();
+                file.read_to_end(&mut output)?;
+                if output.is_empty() {
+                    continue;
+                }
+                *offset += output.len() as u64;
+                printed = true;
+
+                if headers && last_step.as_deref() != Some(step.step_id.as_str()) {
+                    println!("{} Step {}", ">".bright_black(), step.step_id.bold());
+                    *last_step = Some(step.step_id.clone());
+                }
+
+                if is_stderr {
+                    let mut stderr = io::stderr().lock();
+                    stderr.write_all(&output)?;
+                    stderr.flush()?;
+                } else {
+                    let mut stdout = io::stdout().lock();
+                    stdout.write_all(&output)?;
+                    stdout.flush()?;
+                }
+            }
+        }
+
+        Ok(printed)
+    }
+
+    pub fn run(
+        &self,
+        command_args: Vec<String>,
+        step: Option<String>,
+        follow: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let project_folder = get_project_folder();
+
+        if !project_folder.exists() {
+            printdoc! {"
+                    {} Can't print logs.
+
+                      I don't see any existing configuration at {}
+                      Try running {} {}
+                ",
+                ">".bright_black(),
+                project_folder.display().to_string().underline().bold(),
+                EXECUTABLE.get().unwrap(),
+                "init".bright_yellow().bold(),
+            };
+            return Ok(());
+        }
+
+        let Some(run_id) = command_args.first() else {
+            return Err(format!(
+                "Missing run id, find it with '{} history'",
+                EXECUTABLE.get().unwrap()
+            )
+            .into());
+        };
+
+        let Some((directory, mut summary)) =
+            read_runs().into_iter().find(|(_, run)| &run.id == run_id)
+        else {
+            return Err(format!(
+                "No run with id '{}', find it with '{} history'",
+                run_id,
+                EXECUTABLE.get().unwrap()
+            )
+            .into());
+        };
+
+        if let Some(step) = &step {
+            let (stdout, _) = step_log_files(&directory, step);
+            let may_appear = follow && summary.status == RunStatus::Running;
+            if !stdout.exists() && !may_appear {
+                return Err(format!("Run '{}' has no output of step '{}'", run_id, step).into());
+            }
+        }
+
+        // Step output speaks for itself when only one step is printed
+        let headers = step.is_none();
+        if headers {
+            println!(
+                "{} Run {} of {}, started {}: {}",
+                ">".bright_black(),
+                summary.id.bold(),
+                summary.command.bright_yellow().bold(),
+                summary.started_at,
+                summary.status
+            );
+        }
+
+        let mut offsets: HashMap<PathBuf, u64> = HashMap::new();
+        let mut last_step: Option<String> = None;
+
+        loop {
+            // Read before the output, which is complete once the run is over
+            let finished = summary.status != RunStatus::Running;
+            let logs = self.log_files(&directory, &summary, step.as_deref());
+            let printed = self.print_new_output(&logs, &mut offsets, &mut last_step, headers)?;
+
+            if !follow || finished {
+                break;
+            }
+            if !printed {
+                std::thread::sleep(FOLLOW_INTERVAL);
+            }
+            if let Some(latest) = read_run(&directory) {
+                summary = latest;
+            }
+        }
+
+        if headers && follow {
+            println!(
+                "{} Run {} finished: {}",
+                ">".bright_black(),
+                summary.id.bold(),
+                summary.status
+            );
+        }
+
+        Ok(())
+    }
+}
+
+pub const LOGS_COMMAND: LogsCommand = LogsCommand::new();
@@ -119,6 +119,12 @@ usage: "{usage}"
 #           On runtime, it'll accept any of the following inputs:
 #               y | yes | true  | 1
 #               n | no  | false | 0
+#     env_file: String | Vec<String>
+#           [Optional]  default: null
+#           Dotenv files (KEY=value lines) to load into the environment of all steps
+#           Relative to the working directory, later files win over earlier ones
+#           and configuration.environment wins over all of them
+#           Supports @{inputs.*} variable substitution
 #     environment: Map<String, String>
 #           [Optional]  default: null
 #           Environment variables to pass to all steps
@@ -127,10 +133,40 @@ usage: "{usage}"
 #              - Basic values: "SOME_VALUE"
 #              - Input references: "@{inputs.force}"
 #              - OS environment: "${MY_PRIVATE_TOKEN}"
+#     inherit_environment: bool
+#           [Optional]  default: true
+#           Start steps with mici's whole environment
+#           When false, steps only get the variables listed in pass_environment,
+#           the ones set above and the MICI_* variables
+#     pass_environment: Vec<String>
+#           [Optional]  default: null
+#           Variables steps keep with inherit_environment: false, e.g.
+#           [PATH, HOME, "AWS_*"], where * and ? match like in file names
+#           On Windows, most programs also need SYSTEMROOT
+#     secret_environment: Vec<String>
+#           [Optional]  default: null
+#           Variables whose values are masked like secret inputs, wherever
+#           they are set (OS environment, env files, environment maps or MICI_ENV)
 #     working_directory: String
 #           [Optional]  default: null
 #           Working directory for command execution
 #           Defaults to directory where command is invoked
+#     max_parallel: usize
+#           [Optional]  default: null (no limit)
+#           Maximum number of steps to run at the same time
+#     fail_fast: bool
+#           [Optional]  default: true
+#           Cancel the rest of a parallel group as soon as one of its steps fails
+#           When false, the group runs to completion before the failure is reported
+#     timeout: String
+#           [Optional]  default: null
+#           Maximum duration of the whole run, e.g. "30s", "10m" or "1h30m"
+#           A step still running at that point is terminated and the run fails
+#           with exit code 124
+#     soft_fail_exit_code: i32
+#           [Optional]  default: null
+#           Exit code to use when only continue_on_error steps failed
+#           Without it, such runs exit with 0
 #
 configuration:
   confirm: {confirm}
@@ -162,6 +198,9 @@ configuration:
 #       secret: bool
 #           [Optional]  default: false
 #           Hides value in logs/output
+#           Replaced with *** in the output of steps, also when it spans lines
+#           Hand it to later steps through MICI_OUTPUT, @{steps.<id>.output}
+#           is masked too. Interactive steps write to the terminal unmasked
 #       short: String
 #           [Optional]  default: null
 #           Short flag format (e.g., "-n")
@@ -171,6 +210,16 @@ configuration:
 #       default: String
 #           [Optional]  default: null
 #           Default value for the input if not provided
+#       from: Map
+#           [Optional]  default: null
+#           Where a secret input reads its value when not provided, instead of
+#           the shell history, one of:
+#             { file: "~/.config/deploy/token" }      contents of a file
+#             { env: "DEPLOY_TOKEN" }                 an environment variable
+#             { command: "pass show deploy/token" }   the output of a command
+#             { vault: "deploy_token" }               an entry of ~/.mici/secrets.age
+#           The vault holds NAME=value lines, encrypted with age, and is
+#           decrypted with the identity in ~/.mici/identity.txt
 #
 inputs:
   name:
@@ -210,23 +259,93 @@ inputs:
 #           [Optional]  default: null
 #           Human-readable step description
 #       when: String
-#           [Optional]  default: null
+#           [Optional]  default: "on_success()", "always()" for cleanup steps
 #           Conditional expression to control the step execution
+#           Supports:
+#             - Input references: "@{inputs.branch} == 'main'"
+#             - Step results:     "@{steps.build.outcome} == 'success'"
+#             - Matrix values:    "@{matrix.profile} == 'release'"
+#             - Loop items:       "@{item} != 'web'"
+#             - OS environment:   "${DEPLOY_ENV} != 'production'"
+#             - Operators:        ==, !=, &&, ||, ! and parentheses
+#             - Functions:        on_success(), on_failure(), always(),
+#                                 on_platform("linux")
+#           A condition that calls none of on_success(), on_failure() or always()
+#           only applies while no earlier step has failed, e.g.
+#           "@{inputs.env} == 'prod'" runs as "on_success() && (@{inputs.env} == 'prod')"
+#       parallel: bool
+#           [Optional]  default: false
+#           Consecutive steps with parallel set to true run concurrently as a group
+#           Their output lines are prefixed with the step id
+#       depends_on: Vec<String>
+#           [Optional]  default: null
+#           Ids of the steps this step waits for, e.g. [build, lint]
+#           The step starts as soon as all of them finished, concurrently with
+#           any other step that is ready, and is skipped if any of them didn't succeed
+#           Without depends_on, a step waits for every step declared before it
+#       continue_on_error: bool
+#           [Optional]  default: false
+#           A failure of this step is reported in the summary, but doesn't stop
+#           or skip any other step
+#       interactive: bool
+#           [Optional]  default: false
+#           Connects the step to the terminal, for prompts, editors, ssh or psql
+#           Its output isn't captured, and it runs while no other step does
+#           Not allowed together with parallel: true
+#       success_codes: Vec<i32>
+#           [Optional]  default: [0]
+#           Exit codes that count as success, e.g. [0, 3], must not be empty
+#       matrix: Map<String, Vec<String>>
+#           [Optional]  default: null
+#           Runs the step once per combination of the values, e.g.
+#             matrix: { target: [x86_64, aarch64], profile: [dev, release] }
+#           Each run gets the values appended to its id in alphabetical order
+#           of the axes, e.g. build-release-aarch64, and can use them as
+#           @{matrix.target} or the MICI_MATRIX_TARGET environment variable
+#           depends_on: [build] waits for all runs, parallel: true runs them
+#           concurrently
+#       for_each: String | Map
+#           [Optional]  default: null
+#           Runs the step once per item of a list, e.g. "@{inputs.services}"
+#           Items are separated by commas, or by the separator of the map form:
+#             for_each: { items: "@{inputs.hosts}", separator: " " }
+#           Each run gets the item appended to its id, e.g. deploy-api, and
+#           can use it as @{item} and @{item_index} (counting from 0), or the
+#           MICI_ITEM and MICI_ITEM_INDEX environment variables
+#           The summary lists the outcome of every item
+#           An empty list skips the step, like a `when:` that is false
 #       run:
 #         shell: String
 #           [Optional]  default: OS default shell
 #           Shell to execute command (e.g., "bash", "powershell")
+#         env_file: String | Vec<String>
+#           [Optional]  default: null
+#           Dotenv files to load for this step only, on top of everything from
+#           the configuration and below the step's own environment
 #         environment: Map<String, String>
 #           [Optional]  default: null
 #           Override configuration.environment for this step only
 #           Supports same syntax as configuration.environment
+#           plus step results, e.g. "@{steps.version.output}"
 #         working_directory: String
 #           [Optional]  default: configuration.working_directory
 #           Override working directory for this step only
+#           Supports @{inputs.*} and @{steps.*} variable substitution
+#         stdin: String | Map
+#           [Optional]  default: null (empty)
+#           What the step reads from stdin, one of:
+#             inherit                     mici's own stdin, e.g. mici db restore < dump.sql
+#             { text: "..." }             literal text, supports @{inputs.*} and @{steps.*}
+#             { file: "dump.sql" }        a file, relative to the working directory
+#             { step: "<id>" }            the full stdout of an earlier step
+#           A step read from must be over when the step starts: not in the same
+#           parallel group, and listed in depends_on if the step has one
+#           With configuration.confirm and piped stdin, a command that has
+#           stdin: inherit asks for confirmation on the terminal instead
 #         command: String
 #           [Required if no script]
 #           Inline command to execute
-#           Supports @{inputs.*} variable substitution
+#           Supports @{inputs.*} and @{steps.*} variable substitution
 #         script: String
 #           [Required if no command]
 #           Path to an external script file, relative to ~/.mici/jobs/scripts/
@@ -235,6 +354,47 @@ inputs:
 #             shell: "bash"    → bash    ~/.mici/jobs/scripts/deploy.sh
 #             shell: "python3" → python3 ~/.mici/jobs/scripts/deploy.py
 #             shell: "node"    → node    ~/.mici/jobs/scripts/deploy.js
+#         retry: Map
+#           [Optional]  default: null
+#           Run the step again when it fails, e.g. for flaky network operations
+#           attempts: u32
+#             [Optional]  default: 3
+#             Maximum number of runs, including the first one
+#           delay: String
+#             [Optional]  default: "0s"
+#             Time to wait before the next attempt, e.g. "500ms", "10s", "1m"
+#           backoff: f64
+#             [Optional]  default: 1
+#             Multiplies the delay after every attempt, e.g. 2 doubles it
+#           on_exit_codes: Vec<i32>
+#             [Optional]  default: null
+#             Only retry these exit codes, any failure is retried when not set
+#         timeout: String
+#           [Optional]  default: null
+#           Maximum duration of each attempt of this step, e.g. "10m"
+#           The step and every process it started get SIGTERM, followed by
+#           SIGKILL if they are still running 5 seconds later
+#       rollback: Map
+#           [Optional]  default: null
+#           Undoes the step when a later step fails, e.g. restoring a backup
+#           Takes the same fields as run
+#           Rollbacks of all steps that succeeded run in reverse order,
+#           and the command still fails with the error of the failed step
+#
+##  Dry Run
+#
+#   Every command accepts --dry-run, which prints the shell, command or script,
+#   working directory and environment of each step without running anything.
+#   Values of secret inputs are masked. An input using --dry-run itself takes
+#   precedence over it.
+#
+##  Step Selection
+#
+#   Every command also accepts --from-step <id>, --only <id,...> and
+#   --skip <id,...> to resume a command or run part of it. Steps that aren't
+#   selected are skipped and count as done for depends_on. Cleanup steps
+#   always run. Like --dry-run, an input using one of these flags takes
+#   precedence over it.
 #
 ##  Auto-injected Environment Variables
 #
@@ -250,6 +410,63 @@ inputs:
 #     node:       process.env.MICI_INPUT_NAME
 #     powershell: $env:MICI_INPUT_NAME
 #
+##  Step Results
+#
+#   Every finished step exposes its results to the steps that run after it,
+#   in command, environment, working_directory and when fields:
+#     @{steps.<id>.output}    → captured stdout, trimmed
+#     @{steps.<id>.exit_code} → exit code, empty if the step didn't run
+#     @{steps.<id>.outcome}   → success, failure, cancelled or skipped
+#     @{steps.<id>.outputs.<key>} → values written to the MICI_OUTPUT file
+#
+#   MICI_OUTPUT points at a file the step can write key=value lines to,
+#   or multiline values between key<<DELIMITER and DELIMITER lines:
+#     bash:       echo "version=1.2.3" >> "$MICI_OUTPUT"
+#     powershell: "version=1.2.3" | Add-Content $env:MICI_OUTPUT
+#
+#   The same way, lines written to MICI_ENV (NAME=value) are added to the
+#   environment of all later steps, on top of configuration.environment but
+#   below the step's own environment, and directories written to MICI_PATH
+#   are prepended to their PATH:
+#     bash:       echo "$HOME/.local/bin" >> "$MICI_PATH"
+#     powershell: "C:\tools" | Add-Content $env:MICI_PATH
+#
+##  Cleanup Steps
+#
+#   cleanup:
+#     ...
+#     - Same fields as steps
+#
+#   Cleanup steps run after all steps, even when one of them failed or the
+#   command was interrupted with Ctrl-C. The command still exits with the
+#   error of the failed step. Cleanup steps can check how the run went with
+#   @{steps.<id>.outcome}, on_failure() or the MICI_RUN_OUTCOME environment
+#   variable: success, failure or cancelled.
+#
+##  Signals
+#
+#   Every step runs in its own process group. SIGINT (Ctrl-C), SIGTERM and
+#   SIGHUP sent to mici are passed on to the running steps, the remaining
+#   steps are cancelled and cleanup steps run. mici then exits with
+#   128 + signal, e.g. 130 after Ctrl-C. A step killed by a signal is
+#   reported as such, and fails with 128 + signal as well.
+#   Another signal while cleanup steps run is passed on to them the same way,
+#   and the remaining cleanup steps are cancelled. Steps that don't exit
+#   within 5 seconds of a signal are killed.
+#
+##  Run Records
+#
+#   Every run gets an id and a directory, ~/.mici/runs/<command>/<run-id>/,
+#   holding the command with references to inputs resolved (command.yml), the
+#   inputs (inputs.yml), both with secrets masked, the stdout and stderr of every step
+#   (steps/<id>.stdout.log, steps/<id>.stderr.log) and run.yml with the
+#   status, exit codes and timings of the run and its steps, in UTC.
+#
+#   `mici history` lists past runs, filtered by command (`mici history db`) or
+#   status (`--status failure`). `mici logs <run-id>` prints their output,
+#   `--step <id>` that of a single step and `--follow` keeps printing the
+#   output of a run that is still going.
+#
 steps:
   - id: "{step_id}"
     name: "{step_name}"
@@ -1,8 +1,10 @@
 use crate::errors::command::CommandError;
+use crate::utils::resolver::{resolve_item_variables, resolve_matrix_variables};
 use crate::utils::traits::ExportAsHashMap;
 
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::sync::OnceLock;
 
 // Enums
@@ -15,13 +17,83 @@ pub enum CommandSchemaStepRunArgsConfig {
     Map(BTreeMap<String, String>),
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum CommandSchemaStepRunExecution {
     Command { command: String },
     Script { script: String },
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CommandSchemaStepForEach {
+    // String format: "@{inputs.services}", split at commas
+    Items(String),
+    // Object format: { items: "@{inputs.services}", separator: " " }
+    Config {
+        items: String,
+        #[serde(default = "default_schema_step_for_each_separator")]
+        separator: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CommandSchemaEnvFile {
+    // String format: ".env"
+    One(String),
+    // Array format: [".env", ".env.@{inputs.environment}"], later files win
+    Many(Vec<String>),
+}
+
+impl CommandSchemaEnvFile {
+    pub fn paths(&self) -> Vec<&String> {
+        match self {
+            CommandSchemaEnvFile::One(path) => vec![path],
+            CommandSchemaEnvFile::Many(paths) => paths.iter().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CommandSchemaStepRunStdin {
+    // String format: "inherit", forwarding mici's own stdin
+    Source(String),
+    // Object formats: { text: "..." }, { file: "dump.sql" } or { step: "<id>" }
+    Text { text: String },
+    File { file: String },
+    Step { step: String },
+}
+
+impl CommandSchemaStepRunStdin {
+    pub fn is_inherit(&self) -> bool {
+        matches!(self, CommandSchemaStepRunStdin::Source(source) if source == "inherit")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CommandSchemaInputSource {
+    // Object formats: { file: "~/.config/deploy/token" }, { env: "DEPLOY_TOKEN" },
+    // { command: "pass show deploy/token" } or { vault: "deploy_token" }
+    File { file: String },
+    Env { env: String },
+    Command { command: String },
+    Vault { vault: String },
+}
+
+impl fmt::Display for CommandSchemaInputSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandSchemaInputSource::File { file } => write!(f, "file '{}'", file),
+            CommandSchemaInputSource::Env { env } => write!(f, "environment variable '{}'", env),
+            CommandSchemaInputSource::Command { command } => write!(f, "command '{}'", command),
+            CommandSchemaInputSource::Vault { vault } => write!(f, "vault entry '{}'", vault),
+        }
+    }
+}
+
 impl CommandSchemaStepRunExecution {
     pub fn is_command(&self) -> bool {
         matches!(self, CommandSchemaStepRunExecution::Command { .. })
@@ -58,6 +130,7 @@ pub struct CommandSchema {
     pub inputs: Option<BTreeMap<String, CommandSchemaInput>>,
     pub configuration: CommandSchemaConfiguration,
     pub steps: Vec<CommandSchemaStep>,
+    pub cleanup: Option<Vec<CommandSchemaStep>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -73,33 +146,171 @@ pub struct CommandSchemaInput {
     pub short: Option<String>,
     pub long: Option<String>,
     pub default: Option<String>,
+    pub from: Option<CommandSchemaInputSource>,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct CommandSchemaConfiguration {
     #[serde(default)]
     pub confirm: bool,
+    pub env_file: Option<CommandSchemaEnvFile>,
     pub environment: Option<BTreeMap<String, Option<String>>>,
+    #[serde(default = "default_schema_configuration_inherit_environment")]
+    pub inherit_environment: bool,
+    pub pass_environment: Option<Vec<String>>,
+    pub secret_environment: Option<Vec<String>>,
     pub working_directory: Option<String>,
+    pub max_parallel: Option<usize>,
+    #[serde(default = "default_schema_configuration_fail_fast")]
+    pub fail_fast: bool,
+    pub timeout: Option<String>,
+    pub soft_fail_exit_code: Option<i32>,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CommandSchemaStep {
     pub id: String,
     pub name: Option<String>,
     pub when: Option<String>,
+    #[serde(default)]
+    pub parallel: bool,
+    pub depends_on: Option<Vec<String>>,
+    #[serde(default)]
+    pub continue_on_error: bool,
+    #[serde(default)]
+    pub interactive: bool,
+    pub success_codes: Option<Vec<i32>>,
+    pub matrix: Option<BTreeMap<String, Vec<String>>>,
+    pub for_each: Option<CommandSchemaStepForEach>,
     pub run: CommandSchemaStepRun,
+    pub rollback: Option<CommandSchemaStepRun>,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CommandSchemaStepRun {
     #[serde(default = "default_schema_step_run_shell")]
     pub shell: Option<String>,
+    pub env_file: Option<CommandSchemaEnvFile>,
     pub environment: Option<BTreeMap<String, Option<String>>>,
     #[serde(flatten)]
     pub execution: CommandSchemaStepRunExecution,
     pub args: Option<CommandSchemaStepRunArgsConfig>,
     pub working_directory: Option<String>,
+    pub stdin: Option<CommandSchemaStepRunStdin>,
+    pub retry: Option<CommandSchemaStepRunRetry>,
+    pub timeout: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandSchemaStepRunRetry {
+    #[serde(default = "default_schema_step_run_retry_attempts")]
+    pub attempts: u32,
+    pub delay: Option<String>,
+    #[serde(default = "default_schema_step_run_retry_backoff")]
+    pub backoff: f64,
+    pub on_exit_codes: Option<Vec<i32>>,
+}
+
+impl CommandSchemaStep {
+    /// The texts that may hold `@{...}` references: name, `when:`, and the command or script,
+    /// working directory and environment values of `run:` and `rollback:`.
+    pub fn texts(&self) -> Vec<&str> {
+        let mut texts: Vec<&str> = Vec::new();
+        texts.extend(self.name.as_deref());
+        texts.extend(self.when.as_deref());
+        for run in std::iter::once(&self.run).chain(self.rollback.as_ref()) {
+            texts.extend(run.execution.get_command().map(String::as_str));
+            texts.extend(run.execution.get_script().map(String::as_str));
+            texts.extend(run.working_directory.as_deref());
+            texts.extend(
+                run.env_file
+                    .iter()
+                    .flat_map(|f| f.paths())
+                    .map(String::as_str),
+            );
+            match &run.stdin {
+                Some(CommandSchemaStepRunStdin::Text { text }) => texts.push(text),
+                Some(CommandSchemaStepRunStdin::File { file }) => texts.push(file),
+                _ => {}
+            }
+            if let Some(environment) = &run.environment {
+                texts.extend(environment.values().flatten().map(String::as_str));
+            }
+        }
+        texts
+    }
+}
+
+impl CommandSchemaStepRun {
+    /// A copy with `resolve` applied to the command or script, environment values and files,
+    /// working directory and stdin text or file, e.g. to fill in the values of one matrix combination.
+    fn map_text(&self, resolve: &dyn Fn(&str) -> String) -> Self {
+        Self {
+            environment: self.environment.as_ref().map(|environment| {
+                environment
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.as_deref().map(resolve)))
+                    .collect()
+            }),
+            execution: match &self.execution {
+                CommandSchemaStepRunExecution::Command { command } => {
+                    CommandSchemaStepRunExecution::Command {
+                        command: resolve(command),
+                    }
+                }
+                CommandSchemaStepRunExecution::Script { script } => {
+                    CommandSchemaStepRunExecution::Script {
+                        script: resolve(script),
+                    }
+                }
+            },
+            working_directory: self.working_directory.as_deref().map(resolve),
+            env_file: self.env_file.as_ref().map(|env_file| match env_file {
+                CommandSchemaEnvFile::One(path) => CommandSchemaEnvFile::One(resolve(path)),
+                CommandSchemaEnvFile::Many(paths) => {
+                    CommandSchemaEnvFile::Many(paths.iter().map(|path| resolve(path)).collect())
+                }
+            }),
+            stdin: self.stdin.as_ref().map(|stdin| match stdin {
+                CommandSchemaStepRunStdin::Text { text } => CommandSchemaStepRunStdin::Text {
+                    text: resolve(text),
+                },
+                CommandSchemaStepRunStdin::File { file } => CommandSchemaStepRunStdin::File {
+                    file: resolve(file),
+                },
+                other => other.clone(),
+            }),
+            ..self.clone()
+        }
+    }
+}
+
+impl CommandSchemaStepForEach {
+    /// The text to split into items, e.g. `@{inputs.services}`.
+    pub fn items(&self) -> &str {
+        match self {
+            CommandSchemaStepForEach::Items(items) => items,
+            CommandSchemaStepForEach::Config { items, .. } => items,
+        }
+    }
+
+    pub fn separator(&self) -> &str {
+        match self {
+            CommandSchemaStepForEach::Items(_) => ",",
+            CommandSchemaStepForEach::Config { separator, .. } => separator,
+        }
+    }
+}
+
+impl CommandSchemaStepRunRetry {
+    /// Whether a failure with this exit code should be retried.
+    /// Without `on_exit_codes`, every failure is retried.
+    pub fn retries_exit_code(&self, exit_code: Option<i32>) -> bool {
+        match &self.on_exit_codes {
+            Some(codes) => exit_code.is_some_and(|code| codes.contains(&code)),
+            None => true,
+        }
+    }
 }
 
 impl CommandSchema {
@@ -171,7 +382,338 @@ pub fn validate_inputs(
     Ok(())
 }
 
+// Scheduling
+/// Assign each `parallel: true` step the index of the first step of its group.
+/// A group is a run of consecutive parallel steps.
+pub fn parallel_groups(steps: &[CommandSchemaStep]) -> Vec<Option<usize>> {
+    let mut groups = Vec::with_capacity(steps.len());
+    let mut current: Option<usize> = None;
+
+    for (index, step) in steps.iter().enumerate() {
+        if step.parallel {
+            let group = *current.get_or_insert(index);
+            groups.push(Some(group));
+        } else {
+            current = None;
+            groups.push(None);
+        }
+    }
+
+    groups
+}
+
+/// Indices of the steps each step waits for before it can start.
+///
+/// Steps with `depends_on` wait exactly for the listed steps (unknown ids are ignored here,
+/// they're reported by the validator). Steps of a parallel group wait for the steps declared
+/// before the group, and every other step waits for all steps declared before it.
+pub fn step_dependencies(steps: &[CommandSchemaStep]) -> Vec<Vec<usize>> {
+    let groups = parallel_groups(steps);
+
+    steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| match &step.depends_on {
+            Some(depends_on) => {
+                let mut dependencies: Vec<usize> = depends_on
+                    .iter()
+                    .filter_map(|id| steps.iter().position(|s| &s.id == id))
+                    .collect();
+                dependencies.dedup();
+                dependencies
+            }
+            None => (0..groups[index].unwrap_or(index)).collect(),
+        })
+        .collect()
+}
+
+/// For every step, the set of steps it (directly or indirectly) waits for.
+pub fn transitive_dependencies(dependencies: &[Vec<usize>]) -> Vec<HashSet<usize>> {
+    (0..dependencies.len())
+        .map(|index| {
+            let mut seen: HashSet<usize> = HashSet::new();
+            let mut stack: Vec<usize> = dependencies[index].clone();
+
+            while let Some(next) = stack.pop() {
+                if seen.insert(next) {
+                    stack.extend(dependencies[next].iter().copied());
+                }
+            }
+
+            seen
+        })
+        .collect()
+}
+
+/// Replace every step that has a `matrix:` with one step per combination of its values.
+/// Expanded steps get the values appended to their id in the order of the axes' names,
+/// e.g. `build-release-x86_64`. The values are resolved wherever the step uses
+/// `@{matrix.<axis>}` and set as `MICI_MATRIX_<AXIS>` in its environment.
+/// `depends_on` entries naming a matrix step wait for all of its combinations.
+pub fn expand_matrix(steps: &[CommandSchemaStep]) -> Vec<CommandSchemaStep> {
+    let mut expanded_ids: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut expanded: Vec<CommandSchemaStep> = Vec::with_capacity(steps.len());
+
+    for step in steps {
+        let Some(matrix) = &step.matrix else {
+            expanded.push(step.clone());
+            continue;
+        };
+
+        for combination in matrix_combinations(matrix) {
+            let id = matrix_id(&step.id, &combination);
+
+            // Values in `when:` become string literals of the expression
+            let quoted: BTreeMap<String, String> = combination
+                .iter()
+                .map(|(axis, value)| (axis.clone(), quote_literal(value)))
+                .collect();
+
+            let environment = combination
+                .iter()
+                .map(|(axis, value)| {
+                    let key = format!("MICI_MATRIX_{}", axis.to_uppercase().replace('-', "_"));
+                    (key, value.clone())
+                })
+                .collect();
+
+            expanded_ids
+                .entry(step.id.clone())
+                .or_default()
+                .push(id.clone());
+            expanded.push(CommandSchemaStep {
+                matrix: None,
+                ..expand_step(
+                    step,
+                    id,
+                    &|text| resolve_matrix_variables(text, &combination),
+                    &|text| resolve_matrix_variables(text, &quoted),
+                    environment,
+                )
+            });
+        }
+    }
+
+    expand_dependencies(&mut expanded, &expanded_ids);
+    expanded
+}
+
+/// Ids of the runs of a `matrix:` step, none if it has no matrix.
+pub fn matrix_ids(step: &CommandSchemaStep) -> Vec<String> {
+    step.matrix
+        .iter()
+        .flat_map(matrix_combinations)
+        .map(|combination| matrix_id(&step.id, &combination))
+        .collect()
+}
+
+/// Every combination of the values of a matrix, by axis.
+fn matrix_combinations(matrix: &BTreeMap<String, Vec<String>>) -> Vec<BTreeMap<String, String>> {
+    let mut combinations: Vec<BTreeMap<String, String>> = vec![BTreeMap::new()];
+    for (axis, values) in matrix {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|combination| {
+                values.iter().map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.insert(axis.clone(), value.clone());
+                    combination
+                })
+            })
+            .collect();
+    }
+    combinations
+}
+
+fn matrix_id(step_id: &str, combination: &BTreeMap<String, String>) -> String {
+    let suffix: Vec<String> = combination.values().map(|v| id_suffix(v)).collect();
+    format!("{}-{}", step_id, suffix.join("-"))
+}
+
+/// The first of `ids` that appears more than once, e.g. once `matrix:` and `for_each:`
+/// steps are expanded and their values turned into ids.
+pub fn duplicate_step_id<'a>(ids: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let mut seen: HashSet<&str> = HashSet::new();
+    ids.into_iter().find(|id| !seen.insert(id))
+}
+
+/// One run of a `for_each:` step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForEachIteration {
+    /// Id of the step as written in the command file
+    pub step_id: String,
+    pub item: String,
+}
+
+/// Replace every step that has a `for_each:` with one step per item, e.g. `deploy-api`.
+/// `resolve_items` resolves the list, e.g. `@{inputs.services}`, before it's split by the
+/// separator. Items are trimmed and empty ones are dropped. A step without items is kept
+/// as it is, and skipped, so steps waiting on it still do.
+/// `@{item}` and `@{item_index}` (counting from 0) are resolved wherever the step uses them
+/// and set as `MICI_ITEM` and `MICI_ITEM_INDEX` in its environment.
+///
+/// Returns the expanded steps along with the iteration each of them belongs to.
+pub fn expand_for_each(
+    steps: &[CommandSchemaStep],
+    resolve_items: &dyn Fn(&str) -> String,
+) -> (Vec<CommandSchemaStep>, Vec<Option<ForEachIteration>>) {
+    let mut expanded_ids: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut expanded: Vec<CommandSchemaStep> = Vec::with_capacity(steps.len());
+    let mut iterations: Vec<Option<ForEachIteration>> = Vec::with_capacity(steps.len());
+
+    for step in steps {
+        let Some(for_each) = &step.for_each else {
+            expanded.push(step.clone());
+            iterations.push(None);
+            continue;
+        };
+
+        let resolved = resolve_items(for_each.items());
+        let items: Vec<&str> = resolved
+            .split(for_each.separator())
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect();
+
+        if items.is_empty() {
+            tracing::warn!("Step '{}' has no items to run for, skipping it", step.id);
+            expanded.push(CommandSchemaStep {
+                for_each: None,
+                when: Some("false".to_string()),
+                ..step.clone()
+            });
+            iterations.push(None);
+            continue;
+        }
+
+        let ids = expanded_ids.entry(step.id.clone()).or_default();
+        for (index, item) in items.iter().enumerate() {
+            let mut id = format!("{}-{}", step.id, id_suffix(item));
+            if ids.contains(&id) {
+                id = format!("{}-{}", step.id, index);
+            }
+            ids.push(id.clone());
+
+            let values = BTreeMap::from([
+                ("item".to_string(), item.to_string()),
+                ("item_index".to_string(), index.to_string()),
+            ]);
+            let quoted = BTreeMap::from([
+                ("item".to_string(), quote_literal(item)),
+                ("item_index".to_string(), index.to_string()),
+            ]);
+            let environment = BTreeMap::from([
+                ("MICI_ITEM".to_string(), item.to_string()),
+                ("MICI_ITEM_INDEX".to_string(), index.to_string()),
+            ]);
+
+            expanded.push(CommandSchemaStep {
+                for_each: None,
+                ..expand_step(
+                    step,
+                    id,
+                    &|text| resolve_item_variables(text, &values),
+                    &|text| resolve_item_variables(text, &quoted),
+                    environment,
+                )
+            });
+            iterations.push(Some(ForEachIteration {
+                step_id: step.id.clone(),
+                item: item.to_string(),
+            }));
+        }
+    }
+
+    expand_dependencies(&mut expanded, &expanded_ids);
+    (expanded, iterations)
+}
+
+/// A copy of `step` for one of its runs, with `resolve` applied to every text that may
+/// refer to the run's values. `resolve_when` does the same for `when:`, where the values
+/// have to be written as literals. `environment` is added to the step's environment.
+fn expand_step(
+    step: &CommandSchemaStep,
+    id: String,
+    resolve: &dyn Fn(&str) -> String,
+    resolve_when: &dyn Fn(&str) -> String,
+    environment: BTreeMap<String, String>,
+) -> CommandSchemaStep {
+    let mut run = step.run.map_text(resolve);
+    run.environment.get_or_insert_with(BTreeMap::new).extend(
+        environment
+            .into_iter()
+            .map(|(key, value)| (key, Some(value))),
+    );
+
+    CommandSchemaStep {
+        id,
+        name: step.name.as_deref().map(resolve),
+        when: step.when.as_deref().map(resolve_when),
+        run,
+        rollback: step
+            .rollback
+            .as_ref()
+            .map(|rollback| rollback.map_text(resolve)),
+        ..step.clone()
+    }
+}
+
+/// Point `depends_on` entries naming an expanded step at all of its runs.
+fn expand_dependencies(
+    steps: &mut [CommandSchemaStep],
+    expanded_ids: &BTreeMap<String, Vec<String>>,
+) {
+    for step in steps {
+        if let Some(depends_on) = &mut step.depends_on {
+            *depends_on = depends_on
+                .iter()
+                .flat_map(|id| match expanded_ids.get(id) {
+                    Some(ids) => ids.clone(),
+                    None => vec![id.clone()],
+                })
+                .collect();
+        }
+    }
+}
+
+/// A value as part of a step id, with characters that aren't allowed in ids replaced.
+/// Different values may end up the same, which the ids are checked for.
+fn id_suffix(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// A value as a string literal of a `when:` expression.
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
 // Default Functions
+fn default_schema_configuration_fail_fast() -> bool {
+    true
+}
+
+fn default_schema_configuration_inherit_environment() -> bool {
+    true
+}
+
 fn default_schema_step_run_shell() -> Option<String> {
     None
 }
+
+fn default_schema_step_run_retry_attempts() -> u32 {
+    3
+}
+
+fn default_schema_step_run_retry_backoff() -> f64 {
+    1.0
+}
+
+fn default_schema_step_for_each_separator() -> String {
+    ",".to_string()
+}
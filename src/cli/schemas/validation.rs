@@ -1,7 +1,8 @@
 use crate::cli::schemas::v1::*;
 use crate::errors::command::{CommandError, ValidationError};
+use crate::utils::{duration::parse_duration, expression, resolver::matrix_references};
 use miette::{NamedSource, SourceSpan};
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 pub struct SchemaValidator {
     yaml_content: String,
@@ -23,7 +24,14 @@ impl SchemaValidator {
         self.validate_version(&schema.version);
         self.validate_name(&schema.name);
         self.validate_inputs(schema.inputs.as_ref());
-        self.validate_steps(&schema.steps);
+        self.validate_configuration(&schema.configuration);
+        self.validate_steps("steps", &schema.steps);
+        if let Some(cleanup) = &schema.cleanup
+            && !cleanup.is_empty()
+        {
+            self.validate_steps("cleanup", cleanup);
+            self.validate_cleanup_ids(&schema.steps, cleanup);
+        }
 
         if !self.errors.is_empty() {
             let error_count = self.errors.len();
@@ -67,6 +75,7 @@ impl SchemaValidator {
         for (input_name, input) in inputs.iter() {
             self.validate_input_type(input_name, &input.r#type);
             self.validate_input_secret(input_name, &input.r#type, input.secret);
+            self.validate_input_from(input_name, input);
             self.validate_input_options(input_name, &input.r#type, &input.options);
         }
     }
@@ -112,6 +121,19 @@ impl SchemaValidator {
         }
     }
 
+    fn validate_input_from(&mut self, input_name: &str, input: &CommandSchemaInput) {
+        if input.from.is_some()
+            && !input.secret
+            && let Some(span) = self.find_nested_field_span(&["inputs", input_name, "from"])
+        {
+            self.errors.push(ValidationError::InputFromRequiresSecret {
+                src: self.source.clone(),
+                input_name: input_name.to_string(),
+                span,
+            });
+        }
+    }
+
     fn validate_input_options(
         &mut self,
         input_name: &str,
@@ -143,9 +165,35 @@ impl SchemaValidator {
         }
     }
 
-    fn validate_steps(&mut self, steps: &[CommandSchemaStep]) {
+    fn validate_configuration(&mut self, configuration: &CommandSchemaConfiguration) {
+        if configuration.max_parallel == Some(0)
+            && let Some(span) = self.find_nested_field_span(&["configuration", "max_parallel"])
+        {
+            self.errors.push(ValidationError::MaxParallelInvalid {
+                src: self.source.clone(),
+                span,
+            });
+        }
+
+        if let Some(timeout) = &configuration.timeout {
+            self.validate_duration(None, "timeout", timeout);
+        }
+
+        if configuration.inherit_environment
+            && configuration.pass_environment.is_some()
+            && let Some(span) = self.find_nested_field_span(&["configuration", "pass_environment"])
+        {
+            self.errors.push(ValidationError::PassEnvironmentUnused {
+                src: self.source.clone(),
+                span,
+            });
+        }
+    }
+
+    /// Validate the steps listed under `section`, i.e. `steps` or `cleanup`.
+    fn validate_steps(&mut self, section: &str, steps: &[CommandSchemaStep]) {
         if steps.is_empty() {
-            if let Some(span) = self.find_field_span("steps") {
+            if let Some(span) = self.find_field_span(section) {
                 self.errors.push(ValidationError::StepsEmpty {
                     src: self.source.clone(),
                     span,
@@ -156,10 +204,11 @@ impl SchemaValidator {
 
         let mut seen_ids: HashSet<&str> = HashSet::new();
         let mut id_positions: Vec<(&str, usize)> = Vec::new();
+        let waits_for = transitive_dependencies(&step_dependencies(steps));
 
         for (index, step) in steps.iter().enumerate() {
             if step.id.is_empty() {
-                if let Some(span) = self.find_step_field_span(index, "id") {
+                if let Some(span) = self.find_step_field_span(section, index, "id") {
                     self.errors.push(ValidationError::StepIdEmpty {
                         src: self.source.clone(),
                         index,
@@ -167,7 +216,7 @@ impl SchemaValidator {
                     });
                 }
             } else if step.id.contains(char::is_whitespace)
-                && let Some(span) = self.find_step_field_span(index, "id")
+                && let Some(span) = self.find_step_field_span(section, index, "id")
             {
                 self.errors.push(ValidationError::StepIdWhitespace {
                     src: self.source.clone(),
@@ -178,8 +227,8 @@ impl SchemaValidator {
 
             if !seen_ids.insert(&step.id) {
                 if let Some((_, first_index)) = id_positions.iter().find(|(id, _)| *id == step.id) {
-                    let first_span = self.find_step_field_span(*first_index, "id");
-                    let second_span = self.find_step_field_span(index, "id");
+                    let first_span = self.find_step_field_span(section, *first_index, "id");
+                    let second_span = self.find_step_field_span(section, index, "id");
 
                     if let (Some(first_span), Some(second_span)) = (first_span, second_span) {
                         self.errors.push(ValidationError::StepIdDuplicate {
@@ -196,12 +245,12 @@ impl SchemaValidator {
                 id_positions.push((&step.id, index));
             }
 
-            let command_span = self.find_step_field_span(index, "command");
-            let script_span = self.find_step_field_span(index, "script");
+            let command_span = self.find_step_block_field_span(section, index, "run", "command");
+            let script_span = self.find_step_block_field_span(section, index, "run", "script");
 
             match (command_span, script_span) {
                 (None, None) => {
-                    if let Some(span) = self.find_step_field_span(index, "run") {
+                    if let Some(span) = self.find_step_field_span(section, index, "run") {
                         self.errors.push(ValidationError::StepRunMissing {
                             src: self.source.clone(),
                             step_id: step.id.clone(),
@@ -209,24 +258,463 @@ impl SchemaValidator {
                         });
                     }
                 }
-                (Some(_), Some(_)) => {
-                    if let (Some(command_span), Some(script_span)) = (
-                        self.find_step_field_span(index, "command"),
-                        self.find_step_field_span(index, "script"),
-                    ) {
-                        self.errors.push(ValidationError::StepRunMutuallyExclusive {
+                (Some(command_span), Some(script_span)) => {
+                    self.errors.push(ValidationError::StepRunMutuallyExclusive {
+                        src: self.source.clone(),
+                        step_id: step.id.clone(),
+                        block: "run".to_string(),
+                        command_span,
+                        script_span,
+                    });
+                }
+                _ => {
+                    // Valid. Noop.
+                }
+            }
+
+            if step.rollback.is_some()
+                && let (Some(command_span), Some(script_span)) = (
+                    self.find_step_block_field_span(section, index, "rollback", "command"),
+                    self.find_step_block_field_span(section, index, "rollback", "script"),
+                )
+            {
+                self.errors.push(ValidationError::StepRunMutuallyExclusive {
+                    src: self.source.clone(),
+                    step_id: step.id.clone(),
+                    block: "rollback".to_string(),
+                    command_span,
+                    script_span,
+                });
+            }
+
+            if step.interactive
+                && step.parallel
+                && let Some(span) = self.find_step_field_span(section, index, "parallel")
+            {
+                self.errors.push(ValidationError::StepInteractiveParallel {
+                    src: self.source.clone(),
+                    step_id: step.id.clone(),
+                    span,
+                });
+            }
+
+            if step.success_codes.as_ref().is_some_and(|c| c.is_empty())
+                && let Some(span) = self.find_step_field_span(section, index, "success_codes")
+            {
+                self.errors.push(ValidationError::StepSuccessCodesEmpty {
+                    src: self.source.clone(),
+                    step_id: step.id.clone(),
+                    span,
+                });
+            }
+
+            if let Some(when) = &step.when {
+                self.validate_step_when(section, index, &step.id, when);
+            }
+
+            if let Some(retry) = &step.run.retry {
+                self.validate_step_retry(section, index, &step.id, retry);
+            }
+
+            if let Some(timeout) = &step.run.timeout {
+                self.validate_duration(Some((section, index)), "timeout", timeout);
+            }
+
+            self.validate_step_matrix(section, index, step);
+
+            // Rollbacks run once all main steps are over
+            for (block, run, waits_for) in
+                std::iter::once(("run", &step.run, Some(&waits_for[index]))).chain(
+                    step.rollback
+                        .as_ref()
+                        .map(|rollback| ("rollback", rollback, None)),
+                )
+            {
+                if let Some(stdin) = &run.stdin {
+                    self.validate_step_stdin(section, steps, index, waits_for, block, stdin);
+                }
+            }
+
+            if let Some(rollback) = &step.rollback {
+                if let Some(retry) = &rollback.retry {
+                    self.validate_step_retry(section, index, &step.id, retry);
+                }
+                if let Some(timeout) = &rollback.timeout {
+                    self.validate_duration(Some((section, index)), "timeout", timeout);
+                }
+            }
+        }
+
+        self.validate_step_dependencies(section, steps, &id_positions);
+        self.validate_matrix_ids(section, steps);
+    }
+
+    /// Runs of a matrix step get their values appended to its id, which must not make two
+    /// steps end up with the same id.
+    fn validate_matrix_ids(&mut self, section: &str, steps: &[CommandSchemaStep]) {
+        let mut owners: HashMap<String, &str> = steps
+            .iter()
+            .filter(|step| step.matrix.is_none())
+            .map(|step| (step.id.clone(), step.id.as_str()))
+            .collect();
+
+        for (index, step) in steps.iter().enumerate() {
+            for run_id in matrix_ids(step) {
+                let Some(other_step_id) = owners.get(&run_id) else {
+                    owners.insert(run_id, &step.id);
+                    continue;
+                };
+
+                if let Some(span) = self.find_step_field_span(section, index, "matrix") {
+                    self.errors.push(ValidationError::StepMatrixIdDuplicate {
+                        src: self.source.clone(),
+                        step_id: step.id.clone(),
+                        run_id,
+                        other_step_id: other_step_id.to_string(),
+                        span,
+                    });
+                }
+                break;
+            }
+        }
+    }
+
+    /// Cleanup steps share the `@{steps.<id>.*}` namespace with the main steps,
+    /// so their ids must not collide.
+    fn validate_cleanup_ids(&mut self, steps: &[CommandSchemaStep], cleanup: &[CommandSchemaStep]) {
+        for (cleanup_index, cleanup_step) in cleanup.iter().enumerate() {
+            let Some(first_index) = steps.iter().position(|s| s.id == cleanup_step.id) else {
+                continue;
+            };
+
+            let first_span = self.find_step_field_span("steps", first_index, "id");
+            let second_span = self.find_step_field_span("cleanup", cleanup_index, "id");
+
+            if let (Some(first_span), Some(second_span)) = (first_span, second_span) {
+                self.errors.push(ValidationError::StepIdDuplicate {
+                    src: self.source.clone(),
+                    step_id: cleanup_step.id.clone(),
+                    first_span,
+                    second_span,
+                    first_index,
+                    second_index: cleanup_index,
+                });
+            }
+        }
+    }
+
+    /// A step can read the output of the steps declared before it. With `waits_for`, the
+    /// steps it waits for, the source must also be one of them, or it may still be running
+    /// when the step starts.
+    fn validate_step_stdin(
+        &mut self,
+        section: &str,
+        steps: &[CommandSchemaStep],
+        step_index: usize,
+        waits_for: Option<&HashSet<usize>>,
+        block: &str,
+        stdin: &CommandSchemaStepRunStdin,
+    ) {
+        let earlier = &steps[..step_index];
+        let message = match stdin {
+            CommandSchemaStepRunStdin::Source(source) if source != "inherit" => {
+                format!("unknown source '{}'", source)
+            }
+            CommandSchemaStepRunStdin::Step { step } => {
+                match earlier.iter().position(|s| &s.id == step) {
+                    None => format!("step '{}' isn't declared before this step", step),
+                    Some(source) if waits_for.is_some_and(|w| !w.contains(&source)) => format!(
+                        "step '{}' may still be running when this step starts, add it to 'depends_on'",
+                        step
+                    ),
+                    Some(_) => return,
+                }
+            }
+            _ => return,
+        };
+
+        if let Some(span) = self.find_step_block_field_span(section, step_index, block, "stdin") {
+            self.errors.push(ValidationError::StepStdinInvalid {
+                src: self.source.clone(),
+                step_id: steps[step_index].id.clone(),
+                message,
+                span,
+            });
+        }
+    }
+
+    /// Every matrix axis needs values, and `@{matrix.<axis>}` may only refer to
+    /// axes of the step's own matrix.
+    fn validate_step_matrix(&mut self, section: &str, step_index: usize, step: &CommandSchemaStep) {
+        if let Some(matrix) = &step.matrix {
+            if matrix.is_empty()
+                && let Some(span) = self.find_step_field_span(section, step_index, "matrix")
+            {
+                self.errors.push(ValidationError::StepMatrixInvalid {
+                    src: self.source.clone(),
+                    step_id: step.id.clone(),
+                    message: "matrix has no axes".to_string(),
+                    span,
+                });
+            }
+
+            for (axis, values) in matrix {
+                if !values.is_empty() {
+                    continue;
+                }
+                if let Some(span) = self
+                    .find_step_block_field_span(section, step_index, "matrix", axis)
+                    .or_else(|| self.find_step_field_span(section, step_index, "matrix"))
+                {
+                    self.errors.push(ValidationError::StepMatrixInvalid {
+                        src: self.source.clone(),
+                        step_id: step.id.clone(),
+                        message: format!("axis '{}' has no values", axis),
+                        span,
+                    });
+                }
+            }
+        }
+
+        let mut reported: HashSet<String> = HashSet::new();
+        for text in step.texts() {
+            for (axis, range) in matrix_references(text) {
+                if step.matrix.as_ref().is_some_and(|m| m.contains_key(&axis))
+                    || !reported.insert(axis.clone())
+                {
+                    continue;
+                }
+
+                if let Some(span) = self
+                    .find_step_text_span(section, step_index, &text[range])
+                    .or_else(|| self.find_step_field_span(section, step_index, "id"))
+                {
+                    self.errors
+                        .push(ValidationError::StepMatrixReferenceUndefined {
                             src: self.source.clone(),
                             step_id: step.id.clone(),
-                            command_span,
-                            script_span,
+                            axis,
+                            span,
                         });
-                    }
                 }
-                _ => {
-                    // Valid. Noop.
+            }
+        }
+    }
+
+    fn validate_step_retry(
+        &mut self,
+        section: &str,
+        step_index: usize,
+        step_id: &str,
+        retry: &CommandSchemaStepRunRetry,
+    ) {
+        let mut invalid = |field: &str, message: &str| {
+            if let Some(span) = self
+                .find_step_field_span(section, step_index, field)
+                .or_else(|| self.find_step_field_span(section, step_index, "retry"))
+            {
+                self.errors.push(ValidationError::StepRetryInvalid {
+                    src: self.source.clone(),
+                    step_id: step_id.to_string(),
+                    message: message.to_string(),
+                    span,
+                });
+            }
+        };
+
+        if retry.attempts == 0 {
+            invalid("attempts", "'attempts' must be at least 1");
+        }
+        if !(retry.backoff >= 1.0 && retry.backoff.is_finite()) {
+            invalid("backoff", "'backoff' must be at least 1");
+        }
+        if retry.on_exit_codes.as_ref().is_some_and(|c| c.is_empty()) {
+            invalid("on_exit_codes", "'on_exit_codes' must not be empty");
+        }
+
+        if let Some(delay) = &retry.delay {
+            self.validate_duration(Some((section, step_index)), "delay", delay);
+        }
+    }
+
+    /// Validate a duration field of a step, given as its section and index,
+    /// or of the command's configuration when `step` is `None`.
+    fn validate_duration(&mut self, step: Option<(&str, usize)>, field: &str, value: &str) {
+        let Err(message) = parse_duration(value) else {
+            return;
+        };
+
+        let span = match step {
+            Some((section, index)) => self.find_step_field_span(section, index, field),
+            None => self.find_nested_field_span(&["configuration", field]),
+        };
+
+        if let Some(span) = span {
+            self.errors.push(ValidationError::DurationInvalid {
+                src: self.source.clone(),
+                field: field.to_string(),
+                message,
+                span,
+            });
+        }
+    }
+
+    fn validate_step_dependencies(
+        &mut self,
+        section: &str,
+        steps: &[CommandSchemaStep],
+        id_positions: &[(&str, usize)],
+    ) {
+        for (index, step) in steps.iter().enumerate() {
+            let Some(depends_on) = &step.depends_on else {
+                continue;
+            };
+
+            for dependency in depends_on {
+                if !id_positions.iter().any(|(id, _)| id == dependency)
+                    && let Some(span) =
+                        self.find_step_list_entry_span(section, index, "depends_on", dependency)
+                {
+                    self.errors.push(ValidationError::StepDependencyUnknown {
+                        src: self.source.clone(),
+                        step_id: step.id.clone(),
+                        dependency: dependency.clone(),
+                        span,
+                    });
                 }
             }
         }
+
+        let dependencies = step_dependencies(steps);
+        let mut reported: HashSet<BTreeSet<usize>> = HashSet::new();
+
+        for start in 0..steps.len() {
+            let Some(cycle) = find_cycle(&dependencies, start) else {
+                continue;
+            };
+            if !reported.insert(cycle.iter().copied().collect()) {
+                continue;
+            }
+
+            // `cycle[i]` waits for `cycle[i + 1]`, and the last one waits for the first
+            let mut spans = Vec::new();
+            for (position, waiting) in cycle.iter().enumerate() {
+                let waited_on = cycle[(position + 1) % cycle.len()];
+                if let Some(span) = self.find_step_list_entry_span(
+                    section,
+                    *waiting,
+                    "depends_on",
+                    &steps[waited_on].id,
+                ) {
+                    spans.push(span);
+                }
+            }
+
+            let mut names: Vec<&str> = cycle.iter().map(|i| steps[*i].id.as_str()).collect();
+            names.push(&steps[cycle[0]].id);
+
+            self.errors.push(ValidationError::StepDependencyCycle {
+                src: self.source.clone(),
+                cycle: names.join(" -> "),
+                spans,
+            });
+        }
+    }
+
+    fn validate_step_when(&mut self, section: &str, step_index: usize, step_id: &str, when: &str) {
+        let Err(err) = expression::parse(when) else {
+            return;
+        };
+
+        let Some(field_span) = self.find_step_field_span(section, step_index, "when") else {
+            return;
+        };
+
+        // Point into the expression itself when it's written on the same line as `when:`,
+        // otherwise (e.g. block scalars) fall back to the key.
+        let line_start = field_span.offset();
+        let line_end = self.yaml_content[line_start..]
+            .find('\n')
+            .map(|i| line_start + i)
+            .unwrap_or(self.yaml_content.len());
+
+        let span = match self.yaml_content[line_start..line_end].find(when.trim()) {
+            Some(col) if !when.trim().is_empty() => {
+                let leading = when.len() - when.trim_start().len();
+                let offset = (line_start + col + err.offset).saturating_sub(leading);
+                (offset, err.length).into()
+            }
+            _ => field_span,
+        };
+
+        self.errors.push(ValidationError::StepWhenInvalid {
+            src: self.source.clone(),
+            step_id: step_id.to_string(),
+            message: err.message,
+            span,
+        });
+    }
+
+    /// Find a single entry of a list field inside a step, written either inline
+    /// (`depends_on: [build, test]`) or as a block list (`- build`).
+    fn find_step_list_entry_span(
+        &self,
+        section: &str,
+        step_index: usize,
+        field_name: &str,
+        value: &str,
+    ) -> Option<SourceSpan> {
+        let field_span = self.find_step_field_span(section, step_index, field_name)?;
+        let field_offset = field_span.offset();
+
+        let is_id_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+
+        let line_end = self.yaml_content[field_offset..]
+            .find('\n')
+            .map(|i| field_offset + i)
+            .unwrap_or(self.yaml_content.len());
+        let inline = &self.yaml_content[field_offset..line_end];
+
+        if inline.contains('[') {
+            let mut search_from = 0;
+            while let Some(found) = inline[search_from..].find(value) {
+                let start = search_from + found;
+                let end = start + value.len();
+                let before = inline[..start].chars().next_back();
+                let after = inline[end..].chars().next();
+
+                if !before.is_some_and(is_id_char) && !after.is_some_and(is_id_char) {
+                    return Some((field_offset + start, value.len()).into());
+                }
+                search_from = end;
+            }
+            return Some(field_span);
+        }
+
+        let mut offset = line_end + 1;
+        for line in self.yaml_content.get(offset..)?.lines() {
+            let trimmed = line.trim_start();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                offset += line.len() + 1;
+                continue;
+            }
+
+            let Some(item) = trimmed.strip_prefix('-') else {
+                break;
+            };
+            let item = item.trim().trim_matches(|c| c == '"' || c == '\'');
+
+            if item == value
+                && let Some(col) = line.find(value)
+            {
+                return Some((offset + col, value.len()).into());
+            }
+
+            offset += line.len() + 1;
+        }
+
+        Some(field_span)
     }
 
     fn find_field_span(&self, field_name: &str) -> Option<SourceSpan> {
@@ -281,7 +769,12 @@ impl SchemaValidator {
         None
     }
 
-    fn find_step_field_span(&self, step_index: usize, field_name: &str) -> Option<SourceSpan> {
+    fn find_step_field_span(
+        &self,
+        section: &str,
+        step_index: usize,
+        field_name: &str,
+    ) -> Option<SourceSpan> {
         let mut in_steps_block = false;
         let mut steps_indent: Option<usize> = None;
 
@@ -296,7 +789,8 @@ impl SchemaValidator {
             let trimmed = line.trim_start();
 
             if !in_steps_block {
-                if trimmed.starts_with("steps:") {
+                // Sections are top-level keys, e.g. an input named `cleanup` doesn't count
+                if line.starts_with(&format!("{}:", section)) {
                     in_steps_block = true;
                     steps_indent = Some(line.len() - trimmed.len());
                     continue;
@@ -370,4 +864,94 @@ impl SchemaValidator {
 
         None
     }
+
+    /// Find the first occurrence of `text` in the step, searching from its `id:` on.
+    fn find_step_text_span(
+        &self,
+        section: &str,
+        step_index: usize,
+        text: &str,
+    ) -> Option<SourceSpan> {
+        let id_span = self.find_step_field_span(section, step_index, "id")?;
+        let start = self.yaml_content[..id_span.offset()]
+            .rfind('\n')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let found = self.yaml_content[start..].find(text)?;
+
+        Some((start + found, text.len()).into())
+    }
+
+    /// Like `find_step_field_span`, but only looks at the lines nested under the step's
+    /// `block` key, e.g. `command` inside `rollback:` rather than `run:`.
+    fn find_step_block_field_span(
+        &self,
+        section: &str,
+        step_index: usize,
+        block: &str,
+        field_name: &str,
+    ) -> Option<SourceSpan> {
+        let block_span = self.find_step_field_span(section, step_index, block)?;
+
+        let mut offset = 0usize;
+        let mut block_indent: Option<usize> = None;
+
+        for line in self.yaml_content.lines() {
+            let line_start = offset;
+            offset += line.len() + 1;
+            let trimmed = line.trim_start();
+            let indent = line.len() - trimmed.len();
+
+            let Some(block_indent) = block_indent else {
+                if offset > block_span.offset() {
+                    // The span points right behind the block key
+                    block_indent = Some(block_span.offset() - line_start - block.len());
+                }
+                continue;
+            };
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if indent <= block_indent {
+                break;
+            }
+
+            if trimmed.starts_with(&format!("{}:", field_name)) {
+                return Some((line_start + indent + field_name.len(), 1).into());
+            }
+        }
+
+        None
+    }
+}
+
+/// Find a dependency cycle reachable from `start`, returned as the steps along it.
+fn find_cycle(dependencies: &[Vec<usize>], start: usize) -> Option<Vec<usize>> {
+    fn visit(
+        node: usize,
+        dependencies: &[Vec<usize>],
+        path: &mut Vec<usize>,
+        done: &mut HashSet<usize>,
+    ) -> Option<Vec<usize>> {
+        if let Some(position) = path.iter().position(|n| *n == node) {
+            return Some(path[position..].to_vec());
+        }
+        if done.contains(&node) {
+            return None;
+        }
+
+        path.push(node);
+        for next in &dependencies[node] {
+            if let Some(cycle) = visit(*next, dependencies, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.insert(node);
+
+        None
+    }
+
+    visit(start, dependencies, &mut Vec::new(), &mut HashSet::new())
 }
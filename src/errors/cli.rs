@@ -1,7 +1,13 @@
-use crate::errors::command::CommandError;
+use crate::{
+    errors::command::CommandError,
+    runner::signals::{self, describe_exit},
+};
 use miette::Diagnostic;
 use thiserror::Error;
 
+/// Exit code for timed out steps, the same one coreutils' `timeout` uses
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
 #[derive(Error, Debug, Diagnostic)]
 pub enum CliError {
     #[error("{message}")]
@@ -20,8 +26,59 @@ pub enum CliError {
     #[error("Argument error: {0}")]
     ArgParse(String),
 
-    #[error("Step '{step_id}' failed with exit code: {exit_code}")]
-    StepFailed { step_id: String, exit_code: i32 },
+    #[error("Step '{step_id}' {}{}", describe_exit(*.exit_code, *.signal), attempts_suffix(*.attempts))]
+    StepFailed {
+        step_id: String,
+        exit_code: i32,
+        /// The signal that killed the step, `exit_code` is then 128 + signal
+        signal: Option<i32>,
+        attempts: u32,
+    },
+
+    #[error("Step '{step_id}' timed out after {elapsed}")]
+    StepTimedOut { step_id: String, elapsed: String },
+
+    #[error("Steps failed with continue_on_error set: {step_ids}")]
+    StepsSoftFailed { step_ids: String, exit_code: i32 },
+
+    #[error("Interrupted by {}", signals::name(*.signal))]
+    Interrupted { signal: i32 },
+
+    #[error("More than one step runs as '{step_id}'")]
+    #[diagnostic(help(
+        "Runs of matrix and for_each steps get the values or item appended to their id, with characters other than letters, digits, '-' and '_' replaced by '_'. They must not clash with each other or with other steps"
+    ))]
+    DuplicateStepId { step_id: String },
+
+    #[error("Unknown step '{step_id}' given to --{flag}")]
+    #[diagnostic(help("Steps of this command: {available}"))]
+    UnknownStep {
+        flag: String,
+        step_id: String,
+        available: String,
+    },
+}
+
+impl CliError {
+    /// The exit code mici exits with because of this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::StepFailed { exit_code, .. }
+            | CliError::StepsSoftFailed { exit_code, .. } => *exit_code,
+            CliError::StepTimedOut { .. } => TIMEOUT_EXIT_CODE,
+            // 128 + signal, as shells report it
+            CliError::Interrupted { signal } => 128 + signal,
+            _ => 1,
+        }
+    }
+}
+
+fn attempts_suffix(attempts: u32) -> String {
+    if attempts > 1 {
+        format!(" (after {} attempts)", attempts)
+    } else {
+        String::new()
+    }
 }
 
 impl From<String> for CliError {
@@ -84,6 +84,17 @@ pub enum CommandError {
         expected: String,
     },
 
+    #[error("Failed to read input '{input_name}' from {source_name}: {message}")]
+    #[diagnostic(
+        code(mici::input::source_failed),
+        help("Fix the provider, or pass the value with '--{input_name} <value>'")
+    )]
+    InputSourceFailed {
+        input_name: String,
+        source_name: String,
+        message: String,
+    },
+
     #[error("{error_count} working directory error(s)")]
     #[diagnostic(code(mici::runtime::working_directory_errors))]
     WorkingDirectoryErrors {
@@ -91,6 +102,14 @@ pub enum CommandError {
         errors: Vec<WorkingDirectoryError>,
         error_count: usize,
     },
+
+    #[error("{error_count} env file error(s)")]
+    #[diagnostic(code(mici::runtime::env_file_errors))]
+    EnvFileErrors {
+        #[related]
+        errors: Vec<EnvFileError>,
+        error_count: usize,
+    },
 }
 
 #[derive(Error, Debug, Diagnostic)]
@@ -107,6 +126,20 @@ pub struct WorkingDirectoryError {
     pub resolved: String,
 }
 
+#[derive(Error, Debug, Diagnostic)]
+#[error("Env file '{resolved}' does not exist or is not a file")]
+#[diagnostic(
+    code(mici::runtime::env_file_not_found),
+    help("Check that the file exists, relative paths are looked up in the working directory")
+)]
+pub struct EnvFileError {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("this resolved to '{resolved}'")]
+    pub span: SourceSpan,
+    pub resolved: String,
+}
+
 #[derive(Error, Debug, Diagnostic)]
 pub enum ValidationError {
     #[error("Version must be '1' or '1.0', found '{found}'")]
@@ -191,6 +224,21 @@ pub enum ValidationError {
         type_span: SourceSpan,
     },
 
+    #[error("Input '{input_name}' reads its value 'from' a provider but isn't secret")]
+    #[diagnostic(
+        code(mici::schema::input_from_requires_secret),
+        help("Add 'secret: true', providers are meant for values that must stay hidden")
+    )]
+    InputFromRequiresSecret {
+        #[source_code]
+        src: NamedSource<String>,
+
+        input_name: String,
+
+        #[label("only secret inputs can use 'from'")]
+        span: SourceSpan,
+    },
+
     #[error("Input '{input_name}' has type 'choice' but no 'options' provided")]
     #[diagnostic(
         code(mici::schema::choice_requires_options),
@@ -307,6 +355,27 @@ pub enum ValidationError {
         second_index: usize,
     },
 
+    #[error(
+        "Step '{step_id}' has a matrix run with id '{run_id}', which is already used by step '{other_step_id}'"
+    )]
+    #[diagnostic(
+        code(mici::schema::step_matrix_id_duplicate),
+        help(
+            "Characters of matrix values other than letters, digits, '-' and '_' become '_' in the ids of the runs. Use values that stay distinct, or rename the step"
+        )
+    )]
+    StepMatrixIdDuplicate {
+        #[source_code]
+        src: NamedSource<String>,
+
+        step_id: String,
+        run_id: String,
+        other_step_id: String,
+
+        #[label("values of this matrix end up as '{run_id}'")]
+        span: SourceSpan,
+    },
+
     #[error("Step '{step_id}' is missing a 'run' field")]
     #[diagnostic(
         code(mici::schema::step_run_missing),
@@ -323,11 +392,11 @@ pub enum ValidationError {
     },
 
     #[error(
-        "Step '{step_id}' has both 'command' and 'script' in its 'run' - they are mutually exclusive"
+        "Step '{step_id}' has both 'command' and 'script' in its '{block}' - they are mutually exclusive"
     )]
     #[diagnostic(
         code(mici::schema::step_run_mutually_exclusive),
-        help("Only one of 'command' or 'script' may be present in a step's 'run' block")
+        help("Only one of 'command' or 'script' may be present in a step's '{block}' block")
     )]
     StepRunMutuallyExclusive {
         #[source_code]
@@ -335,10 +404,213 @@ pub enum ValidationError {
 
         step_id: String,
 
+        block: String,
+
         #[label("'command' is set here")]
         command_span: SourceSpan,
 
         #[label("'script' is set here")]
         script_span: SourceSpan,
     },
+
+    #[error("Step '{step_id}' has an invalid 'when' expression: {message}")]
+    #[diagnostic(
+        code(mici::schema::step_when_invalid),
+        help(
+            "Expressions support @{{inputs.*}}, ${{ENV}}, ==, !=, &&, ||, !, parentheses and on_success(), on_failure(), always(), on_platform(\"linux\")"
+        )
+    )]
+    StepWhenInvalid {
+        #[source_code]
+        src: NamedSource<String>,
+
+        step_id: String,
+        message: String,
+
+        #[label("{message}")]
+        span: SourceSpan,
+    },
+
+    #[error("'max_parallel' must be at least 1")]
+    #[diagnostic(
+        code(mici::schema::max_parallel_invalid),
+        help(
+            "Remove 'max_parallel' to run parallel steps without a limit, or set it to 1 or more"
+        )
+    )]
+    MaxParallelInvalid {
+        #[source_code]
+        src: NamedSource<String>,
+
+        #[label("must be at least 1")]
+        span: SourceSpan,
+    },
+
+    #[error("'pass_environment' only applies with 'inherit_environment: false'")]
+    #[diagnostic(
+        code(mici::schema::pass_environment_unused),
+        help(
+            "Set 'inherit_environment: false' to start steps with only the listed variables, or remove 'pass_environment'"
+        )
+    )]
+    PassEnvironmentUnused {
+        #[source_code]
+        src: NamedSource<String>,
+
+        #[label("steps already get the whole environment")]
+        span: SourceSpan,
+    },
+
+    #[error("Step '{step_id}' depends on unknown step '{dependency}'")]
+    #[diagnostic(
+        code(mici::schema::step_dependency_unknown),
+        help("'depends_on' entries must be ids of other steps in this command")
+    )]
+    StepDependencyUnknown {
+        #[source_code]
+        src: NamedSource<String>,
+
+        step_id: String,
+        dependency: String,
+
+        #[label("unknown step id")]
+        span: SourceSpan,
+    },
+
+    #[error("Steps have a dependency cycle: {cycle}")]
+    #[diagnostic(
+        code(mici::schema::step_dependency_cycle),
+        help(
+            "Remove one of the dependencies to break the cycle. Steps without 'depends_on' wait for every step declared before them"
+        )
+    )]
+    StepDependencyCycle {
+        #[source_code]
+        src: NamedSource<String>,
+
+        cycle: String,
+
+        #[label(collection, "part of the cycle")]
+        spans: Vec<SourceSpan>,
+    },
+
+    #[error("Step '{step_id}' has an invalid 'retry': {message}")]
+    #[diagnostic(
+        code(mici::schema::step_retry_invalid),
+        help(
+            "'attempts' counts the first run and must be at least 1, 'backoff' multiplies the delay after every attempt and must be at least 1"
+        )
+    )]
+    StepRetryInvalid {
+        #[source_code]
+        src: NamedSource<String>,
+
+        step_id: String,
+        message: String,
+
+        #[label("{message}")]
+        span: SourceSpan,
+    },
+
+    #[error("Step '{step_id}' has an invalid 'matrix': {message}")]
+    #[diagnostic(
+        code(mici::schema::step_matrix_invalid),
+        help("Give every matrix axis at least one value, e.g. target: [x86_64, aarch64]")
+    )]
+    StepMatrixInvalid {
+        #[source_code]
+        src: NamedSource<String>,
+
+        step_id: String,
+        message: String,
+
+        #[label("{message}")]
+        span: SourceSpan,
+    },
+
+    #[error("Step '{step_id}' references undefined matrix axis '{axis}'")]
+    #[diagnostic(
+        code(mici::schema::step_matrix_reference_undefined),
+        help("@{{matrix.<axis>}} can only refer to axes of the step's own 'matrix'")
+    )]
+    StepMatrixReferenceUndefined {
+        #[source_code]
+        src: NamedSource<String>,
+
+        step_id: String,
+        axis: String,
+
+        #[label("not an axis of this step's matrix")]
+        span: SourceSpan,
+    },
+
+    #[error("Step '{step_id}' has an invalid 'stdin': {message}")]
+    #[diagnostic(
+        code(mici::schema::step_stdin_invalid),
+        help(
+            "Use stdin: inherit, or one of {{ text: \"...\" }}, {{ file: \"<path>\" }} or {{ step: \"<id of a step that runs before>\" }}"
+        )
+    )]
+    StepStdinInvalid {
+        #[source_code]
+        src: NamedSource<String>,
+
+        step_id: String,
+        message: String,
+
+        #[label("{message}")]
+        span: SourceSpan,
+    },
+
+    #[error("Step '{step_id}' has no 'success_codes'")]
+    #[diagnostic(
+        code(mici::schema::step_success_codes_empty),
+        help(
+            "List the exit codes that count as success, e.g. [0, 3], or remove the field to only accept 0"
+        )
+    )]
+    StepSuccessCodesEmpty {
+        #[source_code]
+        src: NamedSource<String>,
+
+        step_id: String,
+
+        #[label("no exit code would count as success")]
+        span: SourceSpan,
+    },
+
+    #[error("Step '{step_id}' is interactive and can't run in a parallel group")]
+    #[diagnostic(
+        code(mici::schema::step_interactive_parallel),
+        help(
+            "Interactive steps take over the terminal, so they run on their own. Remove 'parallel: true'"
+        )
+    )]
+    StepInteractiveParallel {
+        #[source_code]
+        src: NamedSource<String>,
+
+        step_id: String,
+
+        #[label("set together with 'interactive: true'")]
+        span: SourceSpan,
+    },
+
+    #[error("Invalid duration for '{field}': {message}")]
+    #[diagnostic(
+        code(mici::schema::duration_invalid),
+        help(
+            "Use a number followed by ms, s, m or h, e.g. \"500ms\", \"30s\", \"10m\" or \"1h30m\""
+        )
+    )]
+    DurationInvalid {
+        #[source_code]
+        src: NamedSource<String>,
+
+        field: String,
+        message: String,
+
+        #[label("{message}")]
+        span: SourceSpan,
+    },
 }
@@ -9,16 +9,27 @@ use crate::{
             config_command::CONFIG_COMMAND,
             edit_command::EDIT_COMMAND,
             fetch_command::FETCH_COMMAND,
+            history_command::HISTORY_COMMAND,
             init_command::INIT_COMMAND,
             list_command::LIST_COMMAND,
+            logs_command::LOGS_COMMAND,
             new_command::NEW_COMMAND,
             validate_command::VALIDATE_COMMAND,
         },
         schemas::v1,
     },
     errors::cli::CliError,
-    runner::{context::ExecutionContext, coordinator::Coordinator},
-    utils::{checks::catch_help_and_version_commands, fs::*, yaml::parse_command_file},
+    runner::{
+        context::{
+            COMMAND_FLAGS, DRY_RUN_FLAG, ExecutionContext, FROM_STEP_FLAG, ONLY_FLAG, SKIP_FLAG,
+            StepSelection, input_long_flag, strip_dashes,
+        },
+        coordinator::Coordinator,
+    },
+    utils::{
+        checks::catch_help_and_version_commands, fs::*, resolver::resolve_input_sources,
+        yaml::parse_command_file,
+    },
 };
 use colored::Colorize;
 use getopts::Options;
@@ -201,6 +212,27 @@ fn run() -> miette::Result<()> {
         Some("config") => {
             CONFIG_COMMAND.run().map_err(CliError::from)?;
         }
+        Some("history") => {
+            opts.optopt("s", "status", "", "");
+            let matches = parse_opts(&opts, &args[1..])?;
+
+            HISTORY_COMMAND
+                .run(matches.free[1..].to_vec(), matches.opt_str("status"))
+                .map_err(CliError::from)?;
+        }
+        Some("logs") => {
+            opts.optopt("", "step", "", "");
+            opts.optflag("f", "follow", "");
+            let matches = parse_opts(&opts, &args[1..])?;
+
+            LOGS_COMMAND
+                .run(
+                    matches.free[1..].to_vec(),
+                    matches.opt_str("step"),
+                    matches.opt_present("follow"),
+                )
+                .map_err(CliError::from)?;
+        }
         Some(_) => {
             run_dynamic_command(&args, &mut opts)?;
         }
@@ -294,18 +326,28 @@ fn run_dynamic_command(args: &[String], opts: &mut Options) -> miette::Result<()
         return Ok(());
     }
 
-    let cmd = parse_command_file(&command_file_path)?;
+    let mut cmd = parse_command_file(&command_file_path)?;
+
+    // Inputs of the command win over mici's own flags
+    let shadowed_flags: Vec<&str> = COMMAND_FLAGS
+        .iter()
+        .filter(|flag| flag.is_shadowed_by(&cmd))
+        .map(|flag| flag.name)
+        .collect();
 
     if let Some(inputs) = &cmd.inputs {
         for (name, input) in inputs {
-            let strip_dashes = |s: &str| s.trim_start_matches('-').to_string();
-
             let short = input.short.as_deref().map(strip_dashes).unwrap_or_default();
-            let long = input
-                .long
-                .as_deref()
-                .map(strip_dashes)
-                .unwrap_or_else(|| name.to_string());
+            let long = input_long_flag(name, input);
+
+            if shadowed_flags.contains(&long.as_str()) {
+                tracing::warn!(
+                    "Input '{}' uses --{}, so it's passed to the command instead of being handled by {}",
+                    name,
+                    long,
+                    EXECUTABLE.get().unwrap()
+                );
+            }
 
             match input.r#type.as_str() {
                 "boolean" | "bool" => {
@@ -318,23 +360,75 @@ fn run_dynamic_command(args: &[String], opts: &mut Options) -> miette::Result<()
         }
     }
 
+    for flag in COMMAND_FLAGS {
+        if shadowed_flags.contains(&flag.name) {
+            continue;
+        }
+        match flag.hint {
+            Some(hint) if flag.multiple => opts.optmulti("", flag.name, flag.description, hint),
+            Some(hint) => opts.optopt("", flag.name, flag.description, hint),
+            None => opts.optflag("", flag.name, flag.description),
+        };
+    }
+
     let matches = parse_opts(opts, option_args)?;
 
-    if let Some(inputs) = &cmd.inputs {
+    let flag_present = |name: &str| !shadowed_flags.contains(&name) && matches.opt_present(name);
+    let flag_list = |name: &str| -> Vec<String> {
+        if shadowed_flags.contains(&name) {
+            return Vec::new();
+        }
+        matches
+            .opt_strs(name)
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    // getopts already rejects `--from-step` given more than once
+    let from_step = matches
+        .opt_str(FROM_STEP_FLAG)
+        .filter(|_| flag_present(FROM_STEP_FLAG))
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty());
+    if let Some(id) = &from_step
+        && id.contains(',')
+    {
+        return Err(CliError::ArgParse(format!(
+            "--{} takes a single step id, got '{}'",
+            FROM_STEP_FLAG, id
+        ))
+        .into());
+    }
+    let selection = StepSelection {
+        from_step,
+        only: flag_list(ONLY_FLAG),
+        skip: flag_list(SKIP_FLAG),
+    };
+
+    if let Some(inputs) = &mut cmd.inputs {
+        resolve_input_sources(inputs, &matches)?;
         v1::validate_inputs(inputs, &matches)?;
     }
 
-    let context = ExecutionContext::new(&cmd, &matches, command_file_path.clone());
+    let context = ExecutionContext::new(&cmd, &matches, command_file_path.clone(), selection);
     let coordinator = Coordinator::with_context(context);
 
+    if flag_present(DRY_RUN_FLAG) {
+        coordinator.plan()?;
+        return Ok(());
+    }
+
     if let Err(e) = coordinator.run() {
         match e {
-            CliError::StepFailed {
-                ref step_id,
-                exit_code,
-            } => {
-                eprintln!("Step '{}' failed with exit code: {}", step_id, exit_code);
-                std::process::exit(exit_code);
+            CliError::StepFailed { .. }
+            | CliError::StepsSoftFailed { .. }
+            | CliError::StepTimedOut { .. }
+            | CliError::Interrupted { .. } => {
+                eprintln!("{}", e);
+                std::process::exit(e.exit_code());
             }
             _ => return Err(e.into()),
         }
@@ -1,6 +1,81 @@
 use std::{collections::BTreeMap, ffi::OsString, path::PathBuf};
 
-use crate::cli::schemas::v1::CommandSchema;
+use crate::cli::schemas::v1::{CommandSchema, CommandSchemaInput};
+
+/// A flag mici adds to every dynamic command, unless one of its inputs uses it.
+pub struct CommandFlag {
+    pub name: &'static str,
+    /// Placeholder of the flag's value, `None` for flags without one
+    pub hint: Option<&'static str>,
+    /// Whether the flag can be given more than once
+    pub multiple: bool,
+    pub description: &'static str,
+}
+
+pub const DRY_RUN_FLAG: &str = "dry-run";
+pub const FROM_STEP_FLAG: &str = "from-step";
+pub const ONLY_FLAG: &str = "only";
+pub const SKIP_FLAG: &str = "skip";
+
+pub const COMMAND_FLAGS: &[CommandFlag] = &[
+    CommandFlag {
+        name: DRY_RUN_FLAG,
+        hint: None,
+        multiple: false,
+        description: "Print the resolved steps without running them",
+    },
+    CommandFlag {
+        name: FROM_STEP_FLAG,
+        hint: Some("ID"),
+        multiple: false,
+        description: "Start at the step with this id, skipping the ones before it",
+    },
+    CommandFlag {
+        name: ONLY_FLAG,
+        hint: Some("ID,..."),
+        multiple: true,
+        description: "Only run the steps with these comma-separated ids",
+    },
+    CommandFlag {
+        name: SKIP_FLAG,
+        hint: Some("ID,..."),
+        multiple: true,
+        description: "Skip the steps with these comma-separated ids",
+    },
+];
+
+impl CommandFlag {
+    /// Whether one of the inputs of `command` uses this flag, which then belongs to the input.
+    pub fn is_shadowed_by(&self, command: &CommandSchema) -> bool {
+        command
+            .inputs_or_empty()
+            .iter()
+            .any(|(name, input)| input_long_flag(name, input) == self.name)
+    }
+}
+
+/// A flag as getopts takes it, e.g. `env` for `--env`. Inputs may be written either way.
+pub fn strip_dashes(flag: &str) -> String {
+    flag.trim_start_matches('-').to_string()
+}
+
+/// The long flag of an input without its dashes, which is its name unless it sets `long:`.
+pub fn input_long_flag(name: &str, input: &CommandSchemaInput) -> String {
+    input
+        .long
+        .as_deref()
+        .map(strip_dashes)
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// Which steps to run, from `--from-step`, `--only` and `--skip`.
+/// Cleanup steps always run.
+#[derive(Debug, Default, Clone)]
+pub struct StepSelection {
+    pub from_step: Option<String>,
+    pub only: Vec<String>,
+    pub skip: Vec<String>,
+}
 
 #[derive(Debug)]
 pub struct ExecutionContext<'a> {
@@ -9,6 +84,7 @@ pub struct ExecutionContext<'a> {
     pub matches: &'a getopts::Matches,
     pub command: &'a CommandSchema,
     pub command_file_path: PathBuf,
+    pub selection: StepSelection,
 }
 
 impl<'a> ExecutionContext<'a> {
@@ -16,6 +92,7 @@ impl<'a> ExecutionContext<'a> {
         command: &'a CommandSchema,
         matches: &'a getopts::Matches,
         command_file_path: PathBuf,
+        selection: StepSelection,
     ) -> Self {
         let os_environment = std::env::vars_os().collect();
         let current_directory = std::env::current_dir().unwrap();
@@ -26,6 +103,7 @@ impl<'a> ExecutionContext<'a> {
             matches,
             command,
             command_file_path,
+            selection,
         }
     }
 }
@@ -1,26 +1,98 @@
 use crate::{
-    cli::schemas::v1::{CommandSchemaStep, CommandSchemaStepRunExecution},
+    cli::schemas::v1::{
+        CommandSchemaEnvFile, CommandSchemaStep, CommandSchemaStepRun,
+        CommandSchemaStepRunExecution, CommandSchemaStepRunStdin, ForEachIteration,
+        duplicate_step_id, expand_for_each, expand_matrix, parallel_groups, step_dependencies,
+        transitive_dependencies,
+    },
     errors::{
         cli::CliError,
-        command::{CommandError, WorkingDirectoryError},
+        command::{CommandError, EnvFileError, WorkingDirectoryError},
+    },
+    runner::{
+        context::{ExecutionContext, FROM_STEP_FLAG, ONLY_FLAG, SKIP_FLAG},
+        files::{RunFiles, StepFiles, read_key_value_file, read_lines_file},
+        output::{StreamOptions, stream_child_output},
+        record::{RunRecord, RunStatus},
+        secrets::Secrets,
+        signals,
+        state::{Exports, StepOutcome, StepResult, StepState},
     },
-    runner::context::ExecutionContext,
     utils::{
-        fs::get_scripts_folder,
-        resolver::{resolve_environment_variables, resolve_input_variables},
+        dotenv::parse_dotenv,
+        duration::{MAX_DURATION, format_duration, parse_duration},
+        expression::{self, ExpressionScope},
+        fs::{get_commands_folder, get_scripts_folder},
+        pattern::matches_glob,
+        resolver::{
+            SECRET_MASK, resolve_environment_variables, resolve_input_variables,
+            resolve_raw_input_value, resolve_runtime_variables, step_references,
+        },
     },
 };
+use colored::Colorize;
 use dialoguer::{Confirm, theme::ColorfulTheme};
-use miette::NamedSource;
-use std::{io::IsTerminal, process::Command};
+use miette::{NamedSource, SourceSpan};
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{BufRead, BufReader, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// What a step reads from its stdin, resolved right before it's started.
+enum StepStdin {
+    /// Nothing, or mici's own stdin (set by `build_command`)
+    Default,
+    Text(Vec<u8>),
+    File(PathBuf),
+}
+
+/// How often the scheduler checks for Ctrl-C while waiting for steps to finish.
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct Coordinator<'a> {
     context: ExecutionContext<'a>,
+    /// Masked in the output of steps and in everything mici prints
+    secrets: Secrets,
+    /// The directory the run is recorded in, once it started
+    record: OnceLock<RunRecord>,
 }
 
 impl<'a> Coordinator<'a> {
     pub fn with_context(context: ExecutionContext<'a>) -> Self {
-        Self { context }
+        let inputs = context.command.inputs_or_empty();
+        let configuration = &context.command.configuration;
+
+        let mut secrets = Secrets::new(
+            inputs
+                .iter()
+                .filter(|(_, input)| input.secret)
+                .map(|(name, input)| resolve_raw_input_value(name, input, context.matches)),
+        );
+
+        // Values of secret variables that are known upfront, env files and steps may add more
+        let environment = resolve_environment_variables(
+            &configuration.environment.clone().unwrap_or_default(),
+            inputs,
+            context.matches,
+        );
+        for name in configuration.secret_environment.iter().flatten() {
+            secrets.extend(environment.get(name).cloned());
+            secrets.extend(std::env::var(name).ok());
+        }
+
+        Self {
+            context,
+            secrets,
+            record: OnceLock::new(),
+        }
     }
 
     pub fn run(&self) -> Result<(), CliError> {
@@ -30,6 +102,8 @@ impl<'a> Coordinator<'a> {
             tracing::info!("  {}", description);
         }
 
+        self.validate_step_selection()?;
+
         if self.context.command.configuration.confirm {
             let confirmation = if std::io::stdin().is_terminal() {
                 println!("> This command requires your confirmation!");
@@ -41,32 +115,15 @@ impl<'a> Coordinator<'a> {
                     .map_err(|e| CliError::General {
                         message: e.to_string(),
                     })?
+            } else if self.forwards_stdin() {
+                // Piped stdin belongs to the steps, so the answer has to come from the terminal
+                confirm_on_terminal()?
             } else {
                 tracing::info!("Command confirmation is piped into the command");
 
                 let mut input = String::new();
                 match std::io::stdin().read_line(&mut input) {
-                    Ok(_) => {
-                        let trimmed = input.trim().to_lowercase();
-
-                        match trimmed.as_str() {
-                            "y" | "yes" | "true" | "1" => {
-                                tracing::debug!("Command confirmed with {}", &trimmed);
-                                true
-                            }
-                            "n" | "no" | "false" | "0" => {
-                                tracing::debug!("Command not confirmed with {}", &trimmed);
-                                false
-                            }
-                            _ => {
-                                tracing::warn!(
-                                    "Piped value '{}' is invalid. Acceptable values: y|yes|true|1 or n|no|false|0",
-                                    &trimmed
-                                );
-                                false
-                            }
-                        }
-                    }
+                    Ok(_) => parse_confirmation(&input),
                     Err(_) => false,
                 }
             };
@@ -78,26 +135,1162 @@ impl<'a> Coordinator<'a> {
         }
 
         self.validate_working_directories()?;
+        self.validate_env_files()?;
+
+        self.start_record();
+        let result = self.execute();
+        if let Some(record) = self.record.get() {
+            let (status, exit_code) = run_status(&result);
+            record.finish(status, exit_code);
+        }
+        result
+    }
 
-        tracing::info!("Executing {} steps", self.context.command.steps.len());
+    /// Run the steps, then the cleanup steps, and tell how the command ended.
+    fn execute(&self) -> Result<(), CliError> {
+        let configuration = &self.context.command.configuration;
 
-        for (index, step) in self.context.command.steps.iter().enumerate() {
-            tracing::info!(
-                "Step {}/{}: {}",
+        // Already validated while parsing the command file.
+        let timeout = match &configuration.timeout {
+            Some(timeout) => Some(parse_duration(timeout).map_err(|e| CliError::General {
+                message: format!("Invalid command timeout: {}", e),
+            })?),
+            None => None,
+        };
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+
+        let files = RunFiles::create().map_err(CliError::from)?;
+        let mut exports = Exports::default();
+
+        signals::install_signal_handlers();
+
+        let (steps, iterations) = self.expand_steps(&self.context.command.steps);
+        let (cleanup_steps, cleanup_iterations) =
+            self.expand_steps(self.context.command.cleanup.as_deref().unwrap_or_default());
+        Self::validate_expanded_ids(&steps, &cleanup_steps)?;
+
+        tracing::info!("Executing {} steps", steps.len());
+
+        // The first step failure is kept and returned once the remaining steps
+        // had a chance to run through their `when:` conditions (e.g. `on_failure()`).
+        let main = self.execute_steps(
+            &steps,
+            &iterations,
+            Phase::Main,
+            &files,
+            deadline,
+            &mut exports,
+        );
+
+        if let Ok(PhaseResult {
+            failure: Some(_),
+            succeeded,
+            ..
+        }) = &main
+        {
+            self.execute_rollbacks(&steps, succeeded, &files, &exports);
+        }
+
+        // Cleanup steps run no matter how the main steps went, but the command
+        // still fails with the main steps' error.
+        let mut cleanup = Ok(PhaseResult::default());
+        if !cleanup_steps.is_empty() {
+            let run_outcome = match &main {
+                Ok(PhaseResult { failure: None, .. }) => StepOutcome::Success,
+                Ok(PhaseResult {
+                    failure: Some(CliError::Interrupted { .. }),
+                    ..
+                }) => StepOutcome::Cancelled,
+                _ => StepOutcome::Failure,
+            };
+            exports
+                .environment
+                .insert("MICI_RUN_OUTCOME".to_string(), run_outcome.to_string());
+
+            tracing::info!("Executing {} cleanup steps", cleanup_steps.len());
+            cleanup = self.execute_steps(
+                &cleanup_steps,
+                &cleanup_iterations,
+                Phase::Cleanup {
+                    after_failure: run_outcome != StepOutcome::Success,
+                },
+                &files,
+                None,
+                &mut exports,
+            );
+        }
+
+        let main = main?;
+        if let Some(e) = main.failure {
+            return Err(e);
+        }
+        let cleanup = cleanup?;
+        if let Some(e) = cleanup.failure {
+            return Err(e);
+        }
+        // A signal that arrived after the main steps still ends the command with its exit code
+        if let Some(signal) = signals::received() {
+            return Err(CliError::Interrupted { signal });
+        }
+
+        let soft_failed: Vec<String> = main
+            .soft_failed
+            .into_iter()
+            .chain(cleanup.soft_failed)
+            .collect();
+        if !soft_failed.is_empty()
+            && let Some(exit_code) = configuration.soft_fail_exit_code
+        {
+            return Err(CliError::StepsSoftFailed {
+                step_ids: soft_failed.join(", "),
+                exit_code,
+            });
+        }
+
+        tracing::info!("Done!");
+        Ok(())
+    }
+
+    /// Create the directory the run is recorded in. Without it, the command still runs.
+    fn start_record(&self) {
+        let inputs: BTreeMap<String, String> = self
+            .context
+            .command
+            .inputs_or_empty()
+            .iter()
+            .map(|(name, input)| {
+                let value = match input.secret {
+                    true => SECRET_MASK.to_string(),
+                    false => self.secrets.mask(&resolve_raw_input_value(
+                        name,
+                        input,
+                        self.context.matches,
+                    )),
+                };
+                (name.clone(), value)
+            })
+            .collect();
+
+        let snapshot = match self.command_snapshot() {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                tracing::warn!("Failed to create a record of this run: {}", e);
+                return;
+            }
+        };
+
+        match RunRecord::create(&self.command_path(), &snapshot, &inputs) {
+            Ok(record) => {
+                tracing::info!(
+                    "Recording run {} in {}",
+                    record.id(),
+                    record.directory().display()
+                );
+                let _ = self.record.set(record);
+            }
+            Err(e) => tracing::warn!("Failed to create a record of this run: {}", e),
+        }
+    }
+
+    /// The command as it runs, for its record: references to inputs are resolved, and
+    /// secrets masked along with the defaults of secret inputs and the values given to
+    /// `secret_environment` variables.
+    fn command_snapshot(&self) -> Result<String, serde_yaml::Error> {
+        let mut snapshot = serde_yaml::to_value(self.context.command)?;
+
+        for (name, input) in self.context.command.inputs_or_empty() {
+            if input.secret
+                && let Some(default) = snapshot
+                    .get_mut("inputs")
+                    .and_then(|inputs| inputs.get_mut(name.as_str()))
+                    .and_then(|input| input.get_mut("default"))
+                    .filter(|default| !default.is_null())
+            {
+                *default = serde_yaml::Value::String(SECRET_MASK.to_string());
+            }
+        }
+        self.resolve_snapshot(&mut snapshot);
+
+        serde_yaml::to_string(&snapshot)
+    }
+
+    /// Resolve and mask every string in `value`, leaving out fields that aren't set.
+    fn resolve_snapshot(&self, value: &mut serde_yaml::Value) {
+        use serde_yaml::Value;
+
+        match value {
+            Value::String(text) => {
+                *text = self.secrets.mask(&resolve_input_variables(
+                    text,
+                    self.context.command.inputs_or_empty(),
+                    self.context.matches,
+                ));
+            }
+            Value::Sequence(items) => {
+                for item in items {
+                    self.resolve_snapshot(item);
+                }
+            }
+            Value::Mapping(mapping) => {
+                mapping.retain(|_, value| !value.is_null());
+                for (key, value) in mapping.iter_mut() {
+                    if key.as_str() == Some("environment")
+                        && let Value::Mapping(environment) = value
+                    {
+                        for (name, value) in environment.iter_mut() {
+                            if name
+                                .as_str()
+                                .is_some_and(|name| self.is_secret_variable(name))
+                            {
+                                *value = Value::String(SECRET_MASK.to_string());
+                            }
+                        }
+                    }
+                    self.resolve_snapshot(value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Path of the command below the commands folder, e.g. `db/restore`.
+    fn command_path(&self) -> String {
+        let path = &self.context.command_file_path;
+        path.strip_prefix(get_commands_folder())
+            .unwrap_or(path)
+            .with_extension("")
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Print what running the command would do without running anything: the shell,
+    /// command or script, working directory and environment of every step, resolved as far
+    /// as possible up front. References to other steps' results are shown as written,
+    /// and values of secret inputs are masked.
+    pub fn plan(&self) -> Result<(), CliError> {
+        self.validate_step_selection()?;
+        self.validate_working_directories()?;
+        self.validate_env_files()?;
+
+        let deselected = self.deselected_steps();
+        let (steps, _) = self.expand_steps(&self.context.command.steps);
+        let (cleanup_steps, _) =
+            self.expand_steps(self.context.command.cleanup.as_deref().unwrap_or_default());
+        Self::validate_expanded_ids(&steps, &cleanup_steps)?;
+
+        println!(
+            "{} Dry run of {}, nothing is executed",
+            ">".bright_black(),
+            self.context.command.name.bold()
+        );
+
+        for (index, step) in steps.iter().enumerate() {
+            println!();
+            if deselected.contains(&step.id) {
+                println!(
+                    "Step {}/{}: {} (skipped, not selected)",
+                    index + 1,
+                    steps.len(),
+                    step.id
+                );
+                continue;
+            }
+            println!("Step {}/{}: {}", index + 1, steps.len(), step.id);
+            self.print_step_plan(step)?;
+        }
+
+        for (index, step) in cleanup_steps.iter().enumerate() {
+            println!();
+            println!(
+                "Cleanup step {}/{}: {}",
                 index + 1,
-                self.context.command.steps.len(),
+                cleanup_steps.len(),
                 step.id
             );
+            self.print_step_plan(step)?;
+        }
 
-            self.execute_step(step)?;
+        Ok(())
+    }
 
-            tracing::info!("Step completed: {}", step.id);
+    fn print_step_plan(&self, step: &CommandSchemaStep) -> Result<(), CliError> {
+        let inputs = self.context.command.inputs_or_empty();
+        let resolve = |text: &str| {
+            self.secrets
+                .mask(&resolve_input_variables(text, inputs, self.context.matches))
+        };
+        let field = |label: &str, value: &str| {
+            println!("  {:<19}{}", format!("{}:", label).bright_black(), value);
+        };
+
+        if let Some(when) = &step.when {
+            field("when", &resolve(when));
+        }
+        if let Some(depends_on) = &step.depends_on {
+            field("depends on", &depends_on.join(", "));
+        }
+        if step.interactive {
+            field("interactive", "yes");
+        }
+
+        let runs = std::iter::once(("", &step.run)).chain(
+            step.rollback
+                .as_ref()
+                .map(|rollback| ("rollback ", rollback)),
+        );
+        for (prefix, run) in runs {
+            field(&format!("{}shell", prefix), step_shell(run));
+            match &run.execution {
+                CommandSchemaStepRunExecution::Command { command } => {
+                    field(&format!("{}command", prefix), &resolve(command));
+                }
+                CommandSchemaStepRunExecution::Script { script } => {
+                    let script_path = get_scripts_folder().join(resolve(script));
+                    field(
+                        &format!("{}script", prefix),
+                        &script_path.display().to_string(),
+                    );
+                }
+            }
+        }
+
+        let working_directory = step
+            .run
+            .working_directory
+            .as_ref()
+            .or(self
+                .context
+                .command
+                .configuration
+                .working_directory
+                .as_ref())
+            .map(|directory| resolve(directory))
+            .unwrap_or_else(|| self.context.current_directory.display().to_string());
+        field("working directory", &working_directory);
+
+        if let Some(stdin) = &step.run.stdin {
+            let source = match stdin {
+                CommandSchemaStepRunStdin::Source(source) => source.clone(),
+                CommandSchemaStepRunStdin::Text { text } => format!("{:?}", resolve(text)),
+                CommandSchemaStepRunStdin::File { file } => format!("file {}", resolve(file)),
+                CommandSchemaStepRunStdin::Step { step } => format!("output of step '{}'", step),
+            };
+            field("stdin", &source);
+        }
+
+        // Values only known at runtime stay as written
+        let directory = match &step.run.working_directory {
+            Some(directory) => self.context.current_directory.join(resolve_input_variables(
+                directory,
+                inputs,
+                self.context.matches,
+            )),
+            None => self.command_directory(),
+        };
+        let environment =
+            self.step_environment(&step.run, &directory, &BTreeMap::new(), &|value| {
+                value.to_string()
+            })?;
+        if !self.context.command.configuration.inherit_environment {
+            let passed: Vec<String> = self.passed_environment().into_keys().collect();
+            match passed.is_empty() {
+                true => field("inherits", "nothing"),
+                false => field("inherits", &passed.join(", ")),
+            }
+        }
+        println!("  {}", "environment:".bright_black());
+        for (key, value) in environment {
+            let value = match self.is_secret_variable(&key) {
+                true => SECRET_MASK.to_string(),
+                false => self.secrets.mask(&value),
+            };
+            println!("    {}={}", key, value);
+        }
+
+        Ok(())
+    }
+
+    /// Whether `name` is listed in `secret_environment`.
+    fn is_secret_variable(&self, name: &str) -> bool {
+        self.context
+            .command
+            .configuration
+            .secret_environment
+            .iter()
+            .flatten()
+            .any(|secret| secret == name)
+    }
+
+    /// The secrets of a step: those of the command, plus the values the step's environment
+    /// gives the variables listed in `secret_environment`.
+    fn step_secrets(&self, cmd: &Command) -> Secrets {
+        let mut secrets = self.secrets.clone();
+        secrets.extend(
+            cmd.get_envs()
+                .filter(|(key, _)| key.to_str().is_some_and(|key| self.is_secret_variable(key)))
+                .filter_map(|(_, value)| value?.to_str().map(str::to_string)),
+        );
+        secrets
+    }
+
+    /// Schedule and run all steps, launching each one as soon as the steps it waits on are done.
+    ///
+    /// Steps with `depends_on` wait for the listed steps only. Consecutive `parallel: true`
+    /// steps form a group that runs concurrently, and every other step waits for all steps
+    /// declared before it. Concurrency is bounded by `configuration.max_parallel`
+    /// (unbounded by default).
+    /// Returns the first step failure, if any. Errors that prevent running steps at all
+    /// (e.g. the shell can't be spawned) are returned as `Err`.
+    ///
+    /// On SIGINT, SIGTERM or SIGHUP, running main steps get the same signal and no further
+    /// ones are started. Cleanup steps, which run after the first signal, are only stopped
+    /// the same way by a signal that arrives while they run.
+    fn execute_steps(
+        &self,
+        steps: &[CommandSchemaStep],
+        iterations: &[Option<ForEachIteration>],
+        phase: Phase,
+        files: &RunFiles,
+        deadline: Option<Instant>,
+        exports: &mut Exports,
+    ) -> Result<PhaseResult, CliError> {
+        let configuration = &self.context.command.configuration;
+        let total = steps.len();
+
+        let groups = parallel_groups(steps);
+        let dependencies = step_dependencies(steps);
+        let ancestors = transitive_dependencies(&dependencies);
+        let max_parallel = configuration.max_parallel.unwrap_or(usize::MAX);
+
+        // `--from-step`, `--only` and `--skip` only apply to the main steps
+        let deselected = match phase {
+            Phase::Main => self.deselected_steps(),
+            Phase::Cleanup { .. } => HashSet::new(),
+        };
+
+        let cancel_flags: Vec<AtomicBool> = steps.iter().map(|_| AtomicBool::new(false)).collect();
+        let mut states: Vec<StepState> = vec![StepState::Pending; total];
+        let mut failed: Vec<usize> = Vec::new();
+        // Failures of `continue_on_error` steps, which don't affect other steps
+        let mut soft_failed: Vec<usize> = Vec::new();
+        let mut succeeded: Vec<usize> = Vec::new();
+        let mut failure: Option<CliError> = None;
+        let mut fatal: Option<CliError> = None;
+        let mut interrupted: Option<i32> = None;
+        let signals_before = signals::count();
+
+        std::thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel::<(usize, Result<StepResult, CliError>)>();
+            let mut running = 0usize;
+
+            loop {
+                let signal = match phase {
+                    Phase::Main => signals::received(),
+                    Phase::Cleanup { .. } => {
+                        signals::latest().filter(|_| signals::count() > signals_before)
+                    }
+                };
+                if interrupted.is_none()
+                    && let Some(signal) = signal
+                {
+                    interrupted = Some(signal);
+                    tracing::warn!(
+                        "Received {}, cancelling running steps",
+                        signals::name(signal)
+                    );
+                    for (index, state) in states.iter().enumerate() {
+                        if *state == StepState::Running {
+                            cancel_flags[index].store(true, Ordering::SeqCst);
+                        }
+                    }
+                }
+
+                for index in 0..total {
+                    if fatal.is_some() || interrupted.is_some() || running >= max_parallel {
+                        break;
+                    }
+                    if states[index] != StepState::Pending
+                        || !dependencies[index].iter().all(|d| states[*d].is_finished())
+                    {
+                        continue;
+                    }
+
+                    let step = &steps[index];
+
+                    if deselected.contains(&step.id) {
+                        tracing::info!(
+                            "Step {}/{}: {} (skipped, not selected)",
+                            index + 1,
+                            total,
+                            step.id
+                        );
+                        states[index] = StepState::Finished(StepOutcome::Skipped);
+                        exports.record(&step.id, &StepResult::skipped());
+                        continue;
+                    }
+
+                    // Interactive steps have the terminal to themselves, so they wait for
+                    // running steps to finish and hold back the ones after them.
+                    let interactive_running = states
+                        .iter()
+                        .zip(steps)
+                        .any(|(state, s)| *state == StepState::Running && s.interactive);
+                    if interactive_running || (step.interactive && running > 0) {
+                        continue;
+                    }
+
+                    // Steps with `depends_on` and no status check in `when:` only run if all
+                    // of their dependencies succeeded, so a failure skips everything downstream
+                    // of it. Dependencies left out with `--from-step`, `--only` or `--skip`
+                    // count as done.
+                    if step.depends_on.is_some()
+                        && !Self::checks_status(step)
+                        && let Some(dependency) = dependencies[index].iter().find(|d| {
+                            states[**d] != StepState::Finished(StepOutcome::Success)
+                                && !soft_failed.contains(d)
+                                && !deselected.contains(&steps[**d].id)
+                        })
+                    {
+                        tracing::info!(
+                            "Step {}/{}: {} (skipped, dependency '{}' did not succeed)",
+                            index + 1,
+                            total,
+                            step.id,
+                            steps[*dependency].id
+                        );
+                        states[index] = StepState::Finished(StepOutcome::Skipped);
+                        exports.record(&step.id, &StepResult::skipped());
+                        continue;
+                    }
+
+                    // Only failures upstream of this step count. With `fail_fast: true`,
+                    // a failure inside a parallel group also stops the rest of that group.
+                    let has_failure = phase.after_failure()
+                        || failed.iter().any(|f| {
+                            ancestors[index].contains(f)
+                                || (configuration.fail_fast
+                                    && groups[*f].is_some()
+                                    && groups[*f] == groups[index])
+                        });
+
+                    match self.should_run(step, phase, has_failure, exports) {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            tracing::info!("Step {}/{}: {} (skipped)", index + 1, total, step.id);
+                            states[index] = StepState::Finished(StepOutcome::Skipped);
+                            exports.record(&step.id, &StepResult::skipped());
+                            continue;
+                        }
+                        Err(e) => {
+                            fatal = Some(e);
+                            break;
+                        }
+                    }
+
+                    let step_files = match files.step_files(&step.id) {
+                        Ok(step_files) => step_files,
+                        Err(e) => {
+                            fatal = Some(CliError::from(e));
+                            break;
+                        }
+                    };
+
+                    tracing::info!("Step {}/{}: {}", index + 1, total, step.id);
+                    states[index] = StepState::Running;
+                    running += 1;
+
+                    let sender = sender.clone();
+                    let cancel = &cancel_flags[index];
+                    let prefix = (groups[index].is_some() || step.depends_on.is_some())
+                        .then_some(step.id.as_str());
+                    let exports = exports.clone();
+
+                    scope.spawn(move || {
+                        let started = SystemTime::now();
+                        let result = self.execute_step(
+                            step,
+                            &exports,
+                            &step_files,
+                            prefix,
+                            cancel,
+                            deadline,
+                        );
+                        if let (Ok(result), Some(record)) = (&result, self.record.get()) {
+                            record.finish_step(&step_files.name, started, result);
+                        }
+                        let _ = sender.send((index, result));
+                    });
+                }
+
+                if running == 0 {
+                    // A skipped step may have unblocked a step declared before it
+                    let ready = (0..total).any(|index| {
+                        states[index] == StepState::Pending
+                            && dependencies[index].iter().all(|d| states[*d].is_finished())
+                    });
+                    if ready && fatal.is_none() && interrupted.is_none() {
+                        continue;
+                    }
+                    break;
+                }
+
+                let (index, result) = match receiver.recv_timeout(INTERRUPT_POLL_INTERVAL) {
+                    Ok(message) => message,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                running -= 1;
+
+                let step = &steps[index];
+                let result = match result {
+                    Ok(result) => result,
+                    Err(e) => {
+                        for (other, state) in states.iter().enumerate() {
+                            if *state == StepState::Running {
+                                cancel_flags[other].store(true, Ordering::SeqCst);
+                            }
+                        }
+                        if fatal.is_none() {
+                            fatal = Some(e);
+                        }
+                        states[index] = StepState::Finished(StepOutcome::Failure);
+                        continue;
+                    }
+                };
+
+                tracing::debug!(
+                    "Captured {} bytes of stdout and {} bytes of stderr from step: {}",
+                    result.output.stdout.len(),
+                    result.output.stderr.len(),
+                    step.id
+                );
+
+                match result.outcome {
+                    StepOutcome::Success => {
+                        tracing::info!("Step completed: {}", step.id);
+                        succeeded.push(index);
+                    }
+                    StepOutcome::Failure => {
+                        let error = if result.timed_out {
+                            CliError::StepTimedOut {
+                                step_id: step.id.clone(),
+                                elapsed: format_duration(result.elapsed),
+                            }
+                        } else {
+                            tracing::error!("Step '{}' {}", step.id, result.describe_exit());
+                            CliError::StepFailed {
+                                step_id: step.id.clone(),
+                                exit_code: result.reported_exit_code(),
+                                signal: result.signal,
+                                attempts: result.attempts,
+                            }
+                        };
+
+                        if step.continue_on_error {
+                            tracing::warn!(
+                                "Continuing after step '{}' failed, it has continue_on_error set",
+                                step.id
+                            );
+                            soft_failed.push(index);
+                        } else {
+                            failed.push(index);
+                            if failure.is_none() {
+                                failure = Some(error);
+                            }
+                        }
+
+                        if !step.continue_on_error
+                            && configuration.fail_fast
+                            && groups[index].is_some()
+                        {
+                            for (sibling, state) in states.iter().enumerate() {
+                                if sibling != index
+                                    && *state == StepState::Running
+                                    && groups[sibling] == groups[index]
+                                {
+                                    tracing::warn!(
+                                        "Cancelling step '{}' after '{}' failed",
+                                        steps[sibling].id,
+                                        step.id
+                                    );
+                                    cancel_flags[sibling].store(true, Ordering::SeqCst);
+                                }
+                            }
+                        }
+                    }
+                    StepOutcome::Cancelled => {
+                        tracing::warn!("Step cancelled: {}", step.id);
+                    }
+                    StepOutcome::Skipped => {}
+                }
+
+                exports.record(&step.id, &result);
+                states[index] = StepState::Finished(result.outcome);
+
+                if let Some(group) = groups[index] {
+                    Self::log_group_summary(steps, &groups, &states, group);
+                }
+            }
+        });
+
+        if let Some(e) = fatal {
+            return Err(e);
+        }
+
+        if let Some(signal) = interrupted {
+            for (index, state) in states.iter_mut().enumerate() {
+                if *state == StepState::Pending {
+                    tracing::info!(
+                        "Step {}/{}: {} (cancelled)",
+                        index + 1,
+                        total,
+                        steps[index].id
+                    );
+                    *state = StepState::Finished(StepOutcome::Cancelled);
+                }
+            }
+            failure = Some(CliError::Interrupted { signal });
+        }
+
+        // Only possible with a dependency cycle, which the validator rejects.
+        let unscheduled: Vec<&str> = states
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| **state == StepState::Pending)
+            .map(|(index, _)| steps[index].id.as_str())
+            .collect();
+        if !unscheduled.is_empty() {
+            return Err(CliError::General {
+                message: format!(
+                    "Steps could not be scheduled due to their dependencies: {}",
+                    unscheduled.join(", ")
+                ),
+            });
+        }
+
+        self.log_summary(steps, iterations, phase, &states, &soft_failed);
+
+        Ok(PhaseResult {
+            failure,
+            soft_failed: soft_failed.iter().map(|i| steps[*i].id.clone()).collect(),
+            succeeded,
+        })
+    }
+
+    /// Expand `matrix:` and `for_each:` steps into one step per run.
+    /// Steps left out by the step selection aren't expanded and are skipped as a whole.
+    fn expand_steps(
+        &self,
+        steps: &[CommandSchemaStep],
+    ) -> (Vec<CommandSchemaStep>, Vec<Option<ForEachIteration>>) {
+        let inputs = self.context.command.inputs_or_empty();
+        let deselected = self.deselected_steps();
+
+        let steps: Vec<CommandSchemaStep> = steps
+            .iter()
+            .map(|step| match deselected.contains(&step.id) {
+                true => CommandSchemaStep {
+                    matrix: None,
+                    for_each: None,
+                    ..step.clone()
+                },
+                false => step.clone(),
+            })
+            .collect();
+
+        expand_for_each(&expand_matrix(&steps), &|items| {
+            resolve_input_variables(items, inputs, self.context.matches)
+        })
+    }
+
+    /// Runs of `for_each` steps get their item appended to their id, which is only known
+    /// once the steps are expanded. Cleanup steps share the ids of the main steps.
+    fn validate_expanded_ids(
+        steps: &[CommandSchemaStep],
+        cleanup_steps: &[CommandSchemaStep],
+    ) -> Result<(), CliError> {
+        let ids = steps
+            .iter()
+            .chain(cleanup_steps)
+            .map(|step| step.id.as_str());
+
+        match duplicate_step_id(ids) {
+            Some(step_id) => Err(CliError::DuplicateStepId {
+                step_id: step_id.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Ids of the main steps left out by `--from-step`, `--only` and `--skip`.
+    fn deselected_steps(&self) -> HashSet<String> {
+        let selection = &self.context.selection;
+        let start = selection
+            .from_step
+            .as_ref()
+            .and_then(|id| self.context.command.steps.iter().position(|s| &s.id == id))
+            .unwrap_or(0);
+
+        self.context
+            .command
+            .steps
+            .iter()
+            .enumerate()
+            .filter(|(index, step)| {
+                *index < start
+                    || (!selection.only.is_empty() && !selection.only.contains(&step.id))
+                    || selection.skip.contains(&step.id)
+            })
+            .map(|(_, step)| step.id.clone())
+            .collect()
+    }
+
+    /// Check that the steps given to `--from-step`, `--only` and `--skip` exist, and warn
+    /// about selected steps referencing results of steps that won't run.
+    fn validate_step_selection(&self) -> Result<(), CliError> {
+        let selection = &self.context.selection;
+        let steps = &self.context.command.steps;
+
+        let given = selection
+            .from_step
+            .iter()
+            .map(|id| (FROM_STEP_FLAG, id))
+            .chain(selection.only.iter().map(|id| (ONLY_FLAG, id)))
+            .chain(selection.skip.iter().map(|id| (SKIP_FLAG, id)));
+        for (flag, id) in given {
+            if !steps.iter().any(|step| &step.id == id) {
+                return Err(CliError::UnknownStep {
+                    flag: flag.to_string(),
+                    step_id: id.clone(),
+                    available: steps
+                        .iter()
+                        .map(|step| step.id.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                });
+            }
+        }
+
+        let deselected = self.deselected_steps();
+        // References to a matrix or for_each run use the expanded id, e.g. `build-linux`
+        let is_deselected = |id: &str| {
+            deselected.contains(id)
+                || steps.iter().any(|step| {
+                    (step.matrix.is_some() || step.for_each.is_some())
+                        && deselected.contains(&step.id)
+                        && id.starts_with(&format!("{}-", step.id))
+                })
+        };
+
+        for step in steps.iter().filter(|step| !deselected.contains(&step.id)) {
+            let mut reported: HashSet<String> = HashSet::new();
+            for text in step.texts() {
+                for (id, field) in step_references(text) {
+                    // The outcome of a step that isn't run is well defined: skipped
+                    if field != "outcome" && is_deselected(&id) && reported.insert(id.clone()) {
+                        tracing::warn!(
+                            "Step '{}' uses results of step '{}', which isn't selected and will be empty",
+                            step.id,
+                            id
+                        );
+                    }
+                }
+            }
         }
 
-        tracing::info!("Done!");
         Ok(())
     }
 
+    /// Undo the steps that succeeded before a failure by running their `rollback:` blocks,
+    /// most recently finished first. A failing rollback is reported, but doesn't stop
+    /// the remaining ones.
+    fn execute_rollbacks(
+        &self,
+        steps: &[CommandSchemaStep],
+        succeeded: &[usize],
+        files: &RunFiles,
+        exports: &Exports,
+    ) {
+        let rollbacks: Vec<CommandSchemaStep> = succeeded
+            .iter()
+            .rev()
+            .map(|index| &steps[*index])
+            .filter_map(|step| {
+                let run = step.rollback.clone()?;
+                Some(CommandSchemaStep {
+                    when: None,
+                    parallel: false,
+                    depends_on: None,
+                    continue_on_error: false,
+                    success_codes: None,
+                    matrix: None,
+                    run,
+                    rollback: None,
+                    ..step.clone()
+                })
+            })
+            .collect();
+
+        if rollbacks.is_empty() {
+            return;
+        }
+
+        tracing::info!("Rolling back {} steps", rollbacks.len());
+
+        let cancel = AtomicBool::new(false);
+        let mut rolled_back: Vec<&str> = Vec::new();
+        let mut failed: Vec<&str> = Vec::new();
+
+        for (index, step) in rollbacks.iter().enumerate() {
+            tracing::info!("Rollback {}/{}: {}", index + 1, rollbacks.len(), step.id);
+
+            // A rollback that can't even start is a failed rollback, not the command's error
+            let started = SystemTime::now();
+            let result = files
+                .step_files(&format!("{}-rollback", step.id))
+                .map_err(CliError::from)
+                .and_then(|step_files| {
+                    let result =
+                        self.execute_step(step, exports, &step_files, None, &cancel, None)?;
+                    if let Some(record) = self.record.get() {
+                        record.finish_step(&step_files.name, started, &result);
+                    }
+                    Ok(result)
+                });
+
+            match result {
+                Ok(result) if result.outcome == StepOutcome::Success => {
+                    tracing::info!("Rollback completed: {}", step.id);
+                    rolled_back.push(&step.id);
+                }
+                Ok(result) => {
+                    tracing::error!("Rollback of step '{}' {}", step.id, result.describe_exit());
+                    failed.push(&step.id);
+                }
+                Err(e) => {
+                    tracing::error!("Rollback of step '{}' could not run: {}", step.id, e);
+                    failed.push(&step.id);
+                }
+            }
+        }
+
+        if !rolled_back.is_empty() {
+            tracing::info!("Rolled back: {}", rolled_back.join(", "));
+        }
+        if !failed.is_empty() {
+            tracing::error!("Rollback failed: {}", failed.join(", "));
+        }
+    }
+
+    /// Log how many steps ended up in which state, and which failures were let through.
+    fn log_summary(
+        &self,
+        steps: &[CommandSchemaStep],
+        iterations: &[Option<ForEachIteration>],
+        phase: Phase,
+        states: &[StepState],
+        soft_failed: &[usize],
+    ) {
+        let counts: Vec<String> = [
+            (StepOutcome::Success, "succeeded"),
+            (StepOutcome::Failure, "failed"),
+            (StepOutcome::Cancelled, "cancelled"),
+            (StepOutcome::Skipped, "skipped"),
+        ]
+        .iter()
+        .filter_map(|(outcome, label)| {
+            let count = states
+                .iter()
+                .filter(|s| **s == StepState::Finished(*outcome))
+                .count();
+            (count > 0).then(|| format!("{} {}", count, label))
+        })
+        .collect();
+
+        let label = match phase {
+            Phase::Main => "Summary",
+            Phase::Cleanup { .. } => "Cleanup summary",
+        };
+        tracing::info!("{}: {}", label, counts.join(", "));
+
+        // Outcome of every `for_each` item, grouped by the step they belong to
+        let mut loops: Vec<(&str, Vec<String>)> = Vec::new();
+        for (iteration, state) in iterations.iter().zip(states) {
+            let Some(iteration) = iteration else {
+                continue;
+            };
+            let outcome = match state {
+                StepState::Finished(outcome) => outcome.to_string(),
+                _ => "pending".to_string(),
+            };
+            let entry = format!("{} ({})", iteration.item, outcome);
+
+            match loops.last_mut() {
+                Some((step_id, entries)) if *step_id == iteration.step_id => entries.push(entry),
+                _ => loops.push((&iteration.step_id, vec![entry])),
+            }
+        }
+        for (step_id, entries) in loops {
+            tracing::info!(
+                "  Step '{}' items: {}",
+                step_id,
+                self.secrets.mask(&entries.join(", "))
+            );
+        }
+
+        for index in soft_failed {
+            tracing::warn!(
+                "  Step '{}' failed, but continue_on_error let the command carry on",
+                steps[*index].id
+            );
+        }
+    }
+
+    /// Log how a parallel group went once all of its steps are finished.
+    fn log_group_summary(
+        steps: &[CommandSchemaStep],
+        groups: &[Option<usize>],
+        states: &[StepState],
+        group: usize,
+    ) {
+        let members: Vec<usize> = (0..steps.len())
+            .filter(|i| groups[*i] == Some(group))
+            .collect();
+
+        if !members.iter().all(|i| states[*i].is_finished()) {
+            return;
+        }
+
+        let summary: Vec<String> = members
+            .iter()
+            .map(|i| match states[*i] {
+                StepState::Finished(outcome) => format!("{} ({})", steps[*i].id, outcome),
+                _ => steps[*i].id.clone(),
+            })
+            .collect();
+
+        if members
+            .iter()
+            .any(|i| states[*i] == StepState::Finished(StepOutcome::Failure))
+        {
+            tracing::error!("Parallel group failed: {}", summary.join(", "));
+        } else {
+            tracing::info!("Parallel group completed: {}", summary.join(", "));
+        }
+    }
+
+    /// Evaluate the step's `when:` condition. Main steps without one only run while
+    /// no previous step has failed, i.e. `on_success()`, cleanup steps always run.
+    /// A condition that calls none of `on_success()`, `on_failure()` or `always()` gets
+    /// the same default, so main steps evaluate it as `on_success() && (<when>)`.
+    fn should_run(
+        &self,
+        step: &CommandSchemaStep,
+        phase: Phase,
+        has_failure: bool,
+        exports: &Exports,
+    ) -> Result<bool, CliError> {
+        let default = matches!(phase, Phase::Cleanup { .. }) || !has_failure;
+        let Some(when) = &step.when else {
+            return Ok(default);
+        };
+
+        // Already validated while parsing the command file.
+        let condition = expression::parse(when).map_err(|e| CliError::General {
+            message: format!("Invalid 'when' expression in step '{}': {}", step.id, e),
+        })?;
+        if !default && !condition.checks_status() {
+            tracing::debug!(
+                "Step '{}' condition '{}' not evaluated after a failure",
+                step.id,
+                when
+            );
+            return Ok(false);
+        }
+
+        let scope = StepScope {
+            coordinator: self,
+            has_failure,
+            exports,
+        };
+        let result = condition.evaluate(&scope).is_truthy();
+
+        tracing::debug!("Step '{}' condition '{}' -> {}", step.id, when, result);
+        Ok(result)
+    }
+
+    /// Whether the step's `when:` calls `on_success()`, `on_failure()` or `always()`.
+    fn checks_status(step: &CommandSchemaStep) -> bool {
+        step.when
+            .as_deref()
+            .and_then(|when| expression::parse(when).ok())
+            .is_some_and(|condition| condition.checks_status())
+    }
+
+    /// Whether any step reads mici's own stdin with `stdin: inherit`.
+    fn forwards_stdin(&self) -> bool {
+        let command = self.context.command;
+        command
+            .steps
+            .iter()
+            .chain(command.cleanup.iter().flatten())
+            .flat_map(|step| std::iter::once(&step.run).chain(step.rollback.as_ref()))
+            .any(|run| run.stdin.as_ref().is_some_and(|stdin| stdin.is_inherit()))
+    }
+
+    /// Resolve what the step reads from stdin. Relative files are looked up in the
+    /// step's working directory.
+    fn step_stdin(
+        &self,
+        step: &CommandSchemaStep,
+        exports: &Exports,
+        cmd: &Command,
+    ) -> Result<StepStdin, CliError> {
+        let stdin = match &step.run.stdin {
+            None => StepStdin::Default,
+            Some(stdin) if stdin.is_inherit() => StepStdin::Default,
+            Some(CommandSchemaStepRunStdin::Text { text }) => {
+                StepStdin::Text(self.resolve_text(text, &exports.variables).into_bytes())
+            }
+            Some(CommandSchemaStepRunStdin::File { file }) => {
+                let file = PathBuf::from(self.resolve_text(file, &exports.variables));
+                let path = match cmd.get_current_dir() {
+                    Some(directory) if file.is_relative() => directory.join(file),
+                    _ => file,
+                };
+                if !path.is_file() {
+                    return Err(CliError::General {
+                        message: format!(
+                            "Stdin file '{}' of step '{}' does not exist",
+                            path.display(),
+                            step.id
+                        ),
+                    });
+                }
+                StepStdin::File(path)
+            }
+            Some(CommandSchemaStepRunStdin::Step { step: source }) => {
+                match exports.stdout.get(source) {
+                    Some(stdout) => StepStdin::Text(stdout.clone().into_bytes()),
+                    None => {
+                        tracing::warn!(
+                            "Step '{}' reads the output of step '{}', which hasn't run",
+                            step.id,
+                            source
+                        );
+                        StepStdin::Text(Vec::new())
+                    }
+                }
+            }
+            // Rejected by the validator
+            Some(CommandSchemaStepRunStdin::Source(_)) => StepStdin::Default,
+        };
+
+        Ok(stdin)
+    }
+
     /// Validate all working directories (config-level + every step) before execution.
     /// Collects all errors and reports them together via miette.
     fn validate_working_directories(&self) -> Result<(), CliError> {
@@ -118,8 +1311,14 @@ impl<'a> Coordinator<'a> {
         }
 
         // Check step-level working_directories
-        for step in &self.context.command.steps {
-            if let Some(step_wd) = &step.run.working_directory {
+        // Directories referring to other steps' results or matrix values are checked
+        // once the step runs.
+        let cleanup = self.context.command.cleanup.iter().flatten();
+        for step in self.context.command.steps.iter().chain(cleanup) {
+            if let Some(step_wd) = &step.run.working_directory
+                && !step_wd.contains("@{steps.")
+                && !step_wd.contains("@{matrix.")
+            {
                 let resolved = resolve_input_variables(step_wd, inputs, self.context.matches);
                 if !std::path::Path::new(&resolved).is_dir()
                     && let Some(e) =
@@ -149,6 +1348,93 @@ impl<'a> Coordinator<'a> {
         resolved: &str,
         search_after: Option<&str>,
     ) -> Option<WorkingDirectoryError> {
+        let (src, span) =
+            Self::key_span(yaml_content, path_str, search_after, "working_directory:")?;
+
+        Some(WorkingDirectoryError {
+            src,
+            span,
+            resolved: resolved.to_string(),
+        })
+    }
+
+    /// Check that the env files of the configuration and of every step exist before
+    /// running anything. Files whose path or directory refers to other steps' results or
+    /// matrix values are checked once the step runs.
+    fn validate_env_files(&self) -> Result<(), CliError> {
+        let inputs = self.context.command.inputs_or_empty();
+        let mut errors: Vec<EnvFileError> = Vec::new();
+
+        let yaml_content = std::fs::read_to_string(&self.context.command_file_path).ok();
+        let path_str = self.context.command_file_path.display().to_string();
+        let is_static = |text: &str| {
+            !text.contains("@{steps.") && !text.contains("@{matrix.") && !text.contains("@{item")
+        };
+
+        let command_directory = self.command_directory();
+        let mut env_files = vec![(
+            None,
+            command_directory.clone(),
+            &self.context.command.configuration.env_file,
+        )];
+
+        let cleanup = self.context.command.cleanup.iter().flatten();
+        for step in self.context.command.steps.iter().chain(cleanup) {
+            for run in std::iter::once(&step.run).chain(step.rollback.as_ref()) {
+                let directory = match &run.working_directory {
+                    Some(directory) if !is_static(directory) => continue,
+                    Some(directory) => self.context.current_directory.join(
+                        resolve_input_variables(directory, inputs, self.context.matches),
+                    ),
+                    None => command_directory.clone(),
+                };
+                env_files.push((Some(step.id.as_str()), directory, &run.env_file));
+            }
+        }
+
+        for (step_id, directory, env_file) in env_files {
+            for path in env_file.iter().flat_map(|env_file| env_file.paths()) {
+                if !is_static(path) {
+                    continue;
+                }
+
+                let resolved =
+                    directory.join(resolve_input_variables(path, inputs, self.context.matches));
+                if resolved.is_file() {
+                    continue;
+                }
+
+                if let Some((src, span)) =
+                    Self::key_span(&yaml_content, &path_str, step_id, "env_file:")
+                {
+                    errors.push(EnvFileError {
+                        src,
+                        span,
+                        resolved: resolved.display().to_string(),
+                    });
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            let error_count = errors.len();
+            return Err(CliError::Command(CommandError::EnvFileErrors {
+                errors,
+                error_count,
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Find `key` in the command file, after the step with the id `search_after` if given,
+    /// otherwise in the configuration block, and span the rest of its line.
+    fn key_span(
+        yaml_content: &Option<String>,
+        path_str: &str,
+        search_after: Option<&str>,
+        key: &str,
+    ) -> Option<(NamedSource<String>, SourceSpan)> {
         let content = yaml_content.as_ref()?;
 
         let search_start = match search_after {
@@ -157,39 +1443,353 @@ impl<'a> Coordinator<'a> {
                 .or_else(|| content.find(&format!("id: '{}'", step_id)))
                 .or_else(|| content.find(&format!("id: {}", step_id)))
                 .unwrap_or(0),
-            None => 0,
+            None => content.find("\nconfiguration:").unwrap_or(0),
         };
 
-        let relative_offset = content[search_start..].find("working_directory:")?;
+        let relative_offset = content[search_start..].find(key)?;
         let offset = search_start + relative_offset;
         let line_end = content[offset..]
             .find('\n')
             .map(|i| offset + i)
             .unwrap_or(content.len());
 
-        Some(WorkingDirectoryError {
-            src: NamedSource::new(path_str, content.clone()),
-            span: (offset, line_end - offset).into(),
-            resolved: resolved.to_string(),
-        })
+        Some((
+            NamedSource::new(path_str, content.clone()),
+            (offset, line_end - offset).into(),
+        ))
     }
 
-    fn execute_step(&self, step: &CommandSchemaStep) -> Result<(), CliError> {
-        let shell = match &step.run.shell {
-            Some(s) => s.as_str(),
-            None => {
-                #[cfg(unix)]
-                {
-                    "bash"
+    /// Resolve `@{inputs.*}` and then `@{steps.*}` references in `text`.
+    fn resolve_text(&self, text: &str, variables: &BTreeMap<String, String>) -> String {
+        let resolved = resolve_input_variables(
+            text,
+            self.context.command.inputs_or_empty(),
+            self.context.matches,
+        );
+        resolve_runtime_variables(&resolved, variables)
+    }
+
+    /// Run a single step, retrying it as configured by `retry:`. A non-zero exit code is
+    /// not an error here, it's reported through the returned `StepResult` and the scheduler
+    /// decides what to do with it.
+    ///
+    /// Every attempt is terminated once it runs longer than the step's `timeout`,
+    /// or when the command's `deadline` passes.
+    fn execute_step(
+        &self,
+        step: &CommandSchemaStep,
+        exports: &Exports,
+        files: &StepFiles,
+        prefix: Option<&str>,
+        cancel: &AtomicBool,
+        deadline: Option<Instant>,
+    ) -> Result<StepResult, CliError> {
+        let mut cmd = self.build_command(step, exports, files)?;
+        let stdin = self.step_stdin(step, exports, &cmd)?;
+        let secrets = self.step_secrets(&cmd);
+
+        // Already validated while parsing the command file.
+        let timeout = match &step.run.timeout {
+            Some(timeout) => Some(parse_duration(timeout).map_err(|e| CliError::General {
+                message: format!("Invalid timeout in step '{}': {}", step.id, e),
+            })?),
+            None => None,
+        };
+
+        // Every step leads its own process group: Ctrl-C in the terminal only reaches mici,
+        // which passes it on, and whatever the step spawned is stopped along with it.
+        // Interactive steps stay in mici's group, which owns the terminal, and so do steps
+        // reading it through `stdin: inherit`.
+        let reads_terminal = step.run.stdin.as_ref().is_some_and(|s| s.is_inherit())
+            && std::io::stdin().is_terminal();
+        let process_group = cfg!(unix) && !step.interactive && !reads_terminal;
+        #[cfg(unix)]
+        if process_group {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+
+        let logs = self
+            .record
+            .get()
+            .and_then(|record| match record.step_logs(&files.name) {
+                Ok(logs) => Some(logs),
+                Err(e) => {
+                    tracing::warn!("Failed to create log files for step '{}': {}", step.id, e);
+                    None
                 }
-                #[cfg(windows)]
-                {
-                    "powershell"
+            });
+
+        let retry = step.run.retry.as_ref();
+        let max_attempts = retry.map(|r| r.attempts.max(1)).unwrap_or(1);
+        let mut delay = match retry.and_then(|r| r.delay.as_deref()) {
+            // Already validated while parsing the command file.
+            Some(delay) => parse_duration(delay).map_err(|e| CliError::General {
+                message: format!("Invalid retry delay in step '{}': {}", step.id, e),
+            })?,
+            None => Duration::ZERO,
+        };
+
+        let mut attempt = 1;
+        loop {
+            if attempt > 1 {
+                tracing::info!("Step '{}' attempt {}/{}", step.id, attempt, max_attempts);
+            }
+
+            let started = Instant::now();
+            let step_deadline = timeout.and_then(|t| started.checked_add(t));
+            let options = StreamOptions {
+                prefix,
+                cancel,
+                deadline: match (step_deadline, deadline) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                },
+                process_group,
+                secrets: &secrets,
+                logs: logs.as_ref(),
+            };
+
+            // Files are opened again for every attempt, to read them from the start
+            match &stdin {
+                StepStdin::Default => {}
+                StepStdin::Text(_) => {
+                    cmd.stdin(Stdio::piped());
+                }
+                StepStdin::File(path) => {
+                    cmd.stdin(std::fs::File::open(path).map_err(CliError::from)?);
                 }
             }
-        };
 
+            let mut child = cmd.spawn().map_err(CliError::from)?;
+            if let (StepStdin::Text(text), Some(mut pipe)) = (&stdin, child.stdin.take()) {
+                let text = text.clone();
+                // Written on its own thread, so a step that reads slowly (or not at all)
+                // doesn't block the output loop. The pipe closes once everything is written.
+                std::thread::spawn(move || {
+                    let _ = pipe.write_all(&text);
+                });
+            }
+            let streamed = stream_child_output(&mut child, &options).map_err(CliError::from)?;
+            let (status, output) = (streamed.status, streamed.output);
+            let elapsed = started.elapsed();
+
+            if streamed.timed_out {
+                let limit = match (step_deadline, deadline) {
+                    (Some(s), Some(c)) if c < s => "command timeout",
+                    (Some(_), _) => "step timeout",
+                    _ => "command timeout",
+                };
+                tracing::error!(
+                    "Step '{}' exceeded the {} after {}",
+                    step.id,
+                    limit,
+                    format_duration(elapsed)
+                );
+            }
+
+            let succeeded = match &step.success_codes {
+                Some(codes) => status.code().is_some_and(|code| codes.contains(&code)),
+                None => status.success(),
+            };
+
+            let outcome = if succeeded && !streamed.timed_out {
+                StepOutcome::Success
+            } else if cancel.load(Ordering::SeqCst) {
+                StepOutcome::Cancelled
+            } else {
+                StepOutcome::Failure
+            };
+
+            let result = StepResult {
+                outcome,
+                exit_code: status.code(),
+                signal: exit_signal(&status),
+                output,
+                outputs: read_key_value_file(&files.output),
+                environment: read_key_value_file(&files.env),
+                path: read_lines_file(&files.path),
+                attempts: attempt,
+                timed_out: streamed.timed_out,
+                elapsed,
+            };
+
+            let Some(retry) = retry else {
+                return Ok(result);
+            };
+            if outcome != StepOutcome::Failure
+                || attempt >= max_attempts
+                || !retry.retries_exit_code(result.exit_code)
+                || deadline.is_some_and(|d| Instant::now() >= d)
+            {
+                return Ok(result);
+            }
+
+            tracing::warn!(
+                "Step '{}' {} (attempt {}/{}), retrying in {}",
+                step.id,
+                result.describe_exit(),
+                attempt,
+                max_attempts,
+                format_duration(delay)
+            );
+
+            if !sleep_unless_cancelled(delay, cancel) {
+                return Ok(StepResult {
+                    outcome: StepOutcome::Cancelled,
+                    ..result
+                });
+            }
+
+            // Only the last attempt's outputs count
+            files.reset().map_err(CliError::from)?;
+            delay = Duration::try_from_secs_f64(delay.as_secs_f64() * retry.backoff)
+                .map_or(MAX_DURATION, |delay| delay.min(MAX_DURATION));
+            attempt += 1;
+        }
+    }
+
+    /// The variables a step gets on top of mici's own environment, or of the variables passed
+    /// with `inherit_environment: false`. Later sources win:
+    /// configuration.env_file, configuration.environment, variables exported by previous
+    /// steps through MICI_ENV, the step's env_file, the step's environment and the
+    /// MICI_INPUT_* variables. The step's env files are looked up in `directory`.
+    /// `resolve` fills in references only known while the command runs, e.g. `@{steps.*}`.
+    fn step_environment(
+        &self,
+        run: &CommandSchemaStepRun,
+        directory: &Path,
+        exported: &BTreeMap<String, String>,
+        resolve: &dyn Fn(&str) -> String,
+    ) -> Result<BTreeMap<String, String>, CliError> {
         let inputs = self.context.command.inputs_or_empty();
+        let configuration = &self.context.command.configuration;
+        let mut environment: BTreeMap<String, String> = BTreeMap::new();
+
+        // Variables of env files are resolved together with the inline ones next to them,
+        // so either can refer to the other with ${NAME}
+        let mut command_variables =
+            self.read_env_files(&configuration.env_file, &self.command_directory(), resolve)?;
+        command_variables.extend(configuration.environment.clone().unwrap_or_default());
+        let resolved_env =
+            resolve_environment_variables(&command_variables, inputs, self.context.matches);
+        for (key, value) in resolved_env {
+            environment.insert(key, resolve(&value));
+        }
+
+        // Variables exported by previous steps through MICI_ENV
+        environment.extend(exported.clone());
+
+        let mut step_variables = self.read_env_files(&run.env_file, directory, resolve)?;
+        step_variables.extend(run.environment.clone().unwrap_or_default());
+        let resolved_env =
+            resolve_environment_variables(&step_variables, inputs, self.context.matches);
+        for (key, value) in resolved_env {
+            environment.insert(key, resolve(&value));
+        }
+
+        // Auto-inject all inputs as MICI_INPUT_* environment variables
+        for (name, input) in inputs {
+            let value = resolve_raw_input_value(name, input, self.context.matches);
+
+            let env_key = format!("MICI_INPUT_{}", name.to_uppercase());
+            environment.insert(env_key, value);
+        }
+
+        Ok(environment)
+    }
+
+    /// The variables of mici's own environment a step keeps with `inherit_environment: false`:
+    /// those matching one of the `pass_environment` patterns.
+    fn passed_environment(&self) -> BTreeMap<String, String> {
+        let patterns = self
+            .context
+            .command
+            .configuration
+            .pass_environment
+            .as_deref()
+            .unwrap_or_default();
+
+        std::env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .filter(|(key, _)| {
+                patterns.iter().any(|pattern| {
+                    // Variable names are case-insensitive on Windows
+                    if cfg!(windows) {
+                        matches_glob(&pattern.to_uppercase(), &key.to_uppercase())
+                    } else {
+                        matches_glob(pattern, key)
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Read the variables of `env_file`, later files winning over earlier ones.
+    /// Relative paths are looked up in `directory`. Paths still referring to values only
+    /// known while the command runs (in dry runs) are left out.
+    fn read_env_files(
+        &self,
+        env_file: &Option<CommandSchemaEnvFile>,
+        directory: &Path,
+        resolve: &dyn Fn(&str) -> String,
+    ) -> Result<BTreeMap<String, Option<String>>, CliError> {
+        let mut variables: BTreeMap<String, Option<String>> = BTreeMap::new();
+
+        for path in env_file.iter().flat_map(|env_file| env_file.paths()) {
+            let path = resolve(&resolve_input_variables(
+                path,
+                self.context.command.inputs_or_empty(),
+                self.context.matches,
+            ));
+            if path.contains("@{") {
+                continue;
+            }
+
+            let path = directory.join(path);
+            let content = std::fs::read_to_string(&path).map_err(|e| CliError::General {
+                message: format!(
+                    "Failed to read env file '{}': {}",
+                    self.secrets.mask(&path.display().to_string()),
+                    e
+                ),
+            })?;
+            variables.extend(
+                parse_dotenv(&content)
+                    .into_iter()
+                    .map(|(key, value)| (key, Some(value))),
+            );
+        }
+
+        Ok(variables)
+    }
+
+    /// The directory steps run in unless they set their own: configuration.working_directory,
+    /// or the one mici was started in.
+    fn command_directory(&self) -> PathBuf {
+        match &self.context.command.configuration.working_directory {
+            Some(directory) => self.context.current_directory.join(resolve_input_variables(
+                directory,
+                self.context.command.inputs_or_empty(),
+                self.context.matches,
+            )),
+            None => self.context.current_directory.clone(),
+        }
+    }
+
+    /// Build the process for a step: shell, resolved command or script, working directory
+    /// and environment.
+    fn build_command(
+        &self,
+        step: &CommandSchemaStep,
+        exports: &Exports,
+        files: &StepFiles,
+    ) -> Result<Command, CliError> {
+        let shell = step_shell(&step.run);
+
+        let inputs = self.context.command.inputs_or_empty();
+
+        let variables = &exports.variables;
 
         let mut cmd = match &step.run.execution {
             CommandSchemaStepRunExecution::Command { command } => {
@@ -202,8 +1802,7 @@ impl<'a> Coordinator<'a> {
                     _ => "-c",
                 };
 
-                let resolved_command =
-                    resolve_input_variables(command, inputs, self.context.matches);
+                let resolved_command = self.resolve_text(command, variables);
 
                 c.arg(flag).arg(&resolved_command);
                 c
@@ -211,7 +1810,7 @@ impl<'a> Coordinator<'a> {
             CommandSchemaStepRunExecution::Script { script } => {
                 let mut c = Command::new(shell);
 
-                let resolved_script = resolve_input_variables(script, inputs, self.context.matches);
+                let resolved_script = self.resolve_text(script, variables);
 
                 let script_path = get_scripts_folder().join(&resolved_script);
 
@@ -227,76 +1826,263 @@ impl<'a> Coordinator<'a> {
         }
 
         if let Some(step_wd) = &step.run.working_directory {
-            let resolved_wd = resolve_input_variables(step_wd, inputs, self.context.matches);
+            let resolved_wd = self.resolve_text(step_wd, variables);
+            if !std::path::Path::new(&resolved_wd).is_dir() {
+                return Err(CliError::General {
+                    message: format!(
+                        "Working directory '{}' of step '{}' does not exist",
+                        self.secrets.mask(&resolved_wd),
+                        step.id
+                    ),
+                });
+            }
             cmd.current_dir(&resolved_wd);
         }
 
         // Set Environment Variables
-        if let Some(command_environment_variables) = &self.context.command.configuration.environment
-        {
-            let resolved_env = resolve_environment_variables(
-                command_environment_variables,
-                inputs,
-                self.context.matches,
-            );
+        let directory = match cmd.get_current_dir() {
+            Some(directory) => self.context.current_directory.join(directory),
+            None => self.context.current_directory.clone(),
+        };
+        let environment =
+            self.step_environment(&step.run, &directory, &exports.environment, &|value| {
+                resolve_runtime_variables(value, variables)
+            })?;
+        let inherit_environment = self.context.command.configuration.inherit_environment;
+        if !inherit_environment {
+            cmd.env_clear();
+            cmd.envs(self.passed_environment());
+        }
+        cmd.envs(environment);
+
+        // Directories exported by previous steps through MICI_PATH
+        if !exports.path.is_empty() {
+            let current_path = cmd
+                .get_envs()
+                .find(|(key, _)| *key == "PATH")
+                .and_then(|(_, value)| value.map(|v| v.to_os_string()))
+                .or_else(|| {
+                    inherit_environment
+                        .then(|| std::env::var_os("PATH"))
+                        .flatten()
+                })
+                .unwrap_or_default();
+
+            let directories = exports
+                .path
+                .iter()
+                .map(std::path::PathBuf::from)
+                .chain(std::env::split_paths(&current_path));
 
-            for (key, value) in resolved_env {
-                cmd.env(key, value);
+            match std::env::join_paths(directories) {
+                Ok(path) => {
+                    cmd.env("PATH", path);
+                }
+                Err(e) => {
+                    tracing::warn!("Ignoring directories from MICI_PATH: {}", e);
+                }
             }
         }
 
-        if let Some(step_environment_variables) = &step.run.environment {
-            let resolved_env = resolve_environment_variables(
-                step_environment_variables,
-                inputs,
-                self.context.matches,
+        cmd.env("MICI_OUTPUT", &files.output)
+            .env("MICI_ENV", &files.env)
+            .env("MICI_PATH", &files.path);
+
+        let inherit_stdin = step.interactive
+            || step
+                .run
+                .stdin
+                .as_ref()
+                .is_some_and(|stdin| stdin.is_inherit());
+        cmd.stdin(match inherit_stdin {
+            true => Stdio::inherit(),
+            false => Stdio::null(),
+        });
+
+        if step.interactive {
+            cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+        } else {
+            cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+
+        Ok(cmd)
+    }
+}
+
+/// Read a piped confirmation answer: y|yes|true|1 or n|no|false|0.
+fn parse_confirmation(input: &str) -> bool {
+    let trimmed = input.trim().to_lowercase();
+
+    match trimmed.as_str() {
+        "y" | "yes" | "true" | "1" => {
+            tracing::debug!("Command confirmed with {}", &trimmed);
+            true
+        }
+        "n" | "no" | "false" | "0" => {
+            tracing::debug!("Command not confirmed with {}", &trimmed);
+            false
+        }
+        _ => {
+            tracing::warn!(
+                "Piped value '{}' is invalid. Acceptable values: y|yes|true|1 or n|no|false|0",
+                &trimmed
             );
+            false
+        }
+    }
+}
+
+/// Ask for confirmation on the controlling terminal, bypassing stdin.
+fn confirm_on_terminal() -> Result<bool, CliError> {
+    #[cfg(unix)]
+    const TERMINAL: &str = "/dev/tty";
+    #[cfg(windows)]
+    const TERMINAL: &str = "CONIN$";
+
+    let terminal = std::fs::File::open(TERMINAL).map_err(|_| CliError::General {
+        message: "No terminal to ask for confirmation on, stdin is forwarded to a step".to_string(),
+    })?;
+
+    eprint!("> Do you want to continue with the execution? [y/n] ");
+    let mut input = String::new();
+    BufReader::new(terminal)
+        .read_line(&mut input)
+        .map_err(CliError::from)?;
+
+    Ok(parse_confirmation(&input))
+}
 
-            for (key, value) in resolved_env {
-                cmd.env(key, value);
+/// How a run ended for its record, with the exit code mici exits with.
+fn run_status(result: &Result<(), CliError>) -> (RunStatus, i32) {
+    match result {
+        Ok(()) => (RunStatus::Success, 0),
+        Err(e @ CliError::Interrupted { .. }) => (RunStatus::Interrupted, e.exit_code()),
+        Err(e) => (RunStatus::Failure, e.exit_code()),
+    }
+}
+
+/// The shell a step runs in, or the interpreter of its script.
+fn step_shell(run: &CommandSchemaStepRun) -> &str {
+    match &run.shell {
+        Some(s) => s.as_str(),
+        None => {
+            #[cfg(unix)]
+            {
+                "bash"
+            }
+            #[cfg(windows)]
+            {
+                "powershell"
             }
         }
+    }
+}
 
-        // Auto-inject all inputs as MICI_INPUT_* environment variables
-        for (name, input) in inputs {
-            let value = match input.r#type.as_str() {
-                "boolean" | "bool" => {
-                    if self.context.matches.opt_present(name) {
-                        "true".to_string()
-                    } else {
-                        input.default.as_deref().unwrap_or("false").to_string()
-                    }
-                }
-                _ => self
-                    .context
-                    .matches
-                    .opt_str(name)
-                    .or_else(|| input.default.clone())
-                    .unwrap_or_default(),
-            };
+/// The signal that killed a process, `None` if it exited on its own.
+fn exit_signal(status: &ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+    #[cfg(windows)]
+    {
+        let _ = status;
+        None
+    }
+}
 
-            let env_key = format!("MICI_INPUT_{}", name.to_uppercase());
-            cmd.env(env_key, value);
+/// Sleep for `duration`, waking up early if `cancel` is set.
+/// Returns false when cancelled.
+fn sleep_unless_cancelled(duration: Duration, cancel: &AtomicBool) -> bool {
+    let deadline = Instant::now().checked_add(duration.min(MAX_DURATION));
+
+    loop {
+        if cancel.load(Ordering::SeqCst) {
+            return false;
         }
 
-        let output = cmd.output().map_err(CliError::from)?;
+        let now = Instant::now();
+        let remaining = deadline.map_or(Duration::MAX, |d| d.saturating_duration_since(now));
+        if remaining.is_zero() {
+            return true;
+        }
+        std::thread::sleep(remaining.min(Duration::from_millis(100)));
+    }
+}
+
+/// Main steps run first, cleanup steps after them no matter how the main steps went.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Main,
+    Cleanup { after_failure: bool },
+}
+
+impl Phase {
+    /// Whether the main steps failed, which `on_failure()` in cleanup steps picks up.
+    fn after_failure(&self) -> bool {
+        matches!(
+            self,
+            Phase::Cleanup {
+                after_failure: true
+            }
+        )
+    }
+}
+
+/// How the steps of one phase went.
+#[derive(Default)]
+struct PhaseResult {
+    /// The first failure, if any
+    failure: Option<CliError>,
+    /// Ids of failed `continue_on_error` steps
+    soft_failed: Vec<String>,
+    /// Indices of the steps that succeeded, in the order they finished
+    succeeded: Vec<usize>,
+}
+
+/// Lookups for `when:` expressions of a single step.
+struct StepScope<'c, 'a> {
+    coordinator: &'c Coordinator<'a>,
+    has_failure: bool,
+    exports: &'c Exports,
+}
 
-        if !output.stdout.is_empty() {
-            print!("{}", String::from_utf8_lossy(&output.stdout));
+impl ExpressionScope for StepScope<'_, '_> {
+    fn variable(&self, path: &str) -> Option<String> {
+        if path.starts_with("steps.") {
+            return self.exports.variables.get(path).cloned();
         }
-        if !output.stderr.is_empty() {
-            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+
+        let context = &self.coordinator.context;
+        let name = path.strip_prefix("inputs.")?;
+        let input = context.command.inputs_or_empty().get(name)?;
+
+        Some(resolve_raw_input_value(name, input, context.matches))
+    }
+
+    fn environment(&self, name: &str) -> Option<String> {
+        let context = &self.coordinator.context;
+
+        if let Some(value) = self.exports.environment.get(name) {
+            return Some(value.clone());
         }
 
-        if !output.status.success() {
-            let exit_code = output.status.code().unwrap_or(1);
-            tracing::error!("Step '{}' failed with exit code: {}", step.id, exit_code);
-            return Err(CliError::StepFailed {
-                step_id: step.id.clone(),
-                exit_code,
-            });
+        if let Some(command_environment_variables) = &context.command.configuration.environment {
+            let resolved = resolve_environment_variables(
+                command_environment_variables,
+                context.command.inputs_or_empty(),
+                context.matches,
+            );
+            if let Some(value) = resolved.get(name) {
+                return Some(value.clone());
+            }
         }
 
-        Ok(())
+        std::env::var(name).ok()
+    }
+
+    fn has_failure(&self) -> bool {
+        self.has_failure
     }
 }
@@ -0,0 +1,234 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    hash::{BuildHasher, RandomState},
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// How many random directory names to try before giving up.
+const CREATE_ATTEMPTS: u32 = 16;
+
+/// Temporary directory holding the files steps use to hand values to later steps.
+/// Removed again once the run is over.
+pub struct RunFiles {
+    directory: PathBuf,
+    counter: AtomicUsize,
+}
+
+/// Files handed to a single step execution through `MICI_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct StepFiles {
+    /// What the files are for, e.g. `deploy` or `deploy-rollback`
+    pub name: String,
+    /// `MICI_OUTPUT`: `key=value` lines exposed as `@{steps.<id>.outputs.<key>}`
+    pub output: PathBuf,
+    /// `MICI_ENV`: `NAME=value` lines added to the environment of later steps
+    pub env: PathBuf,
+    /// `MICI_PATH`: directories prepended to `PATH` for later steps, one per line
+    pub path: PathBuf,
+}
+
+impl RunFiles {
+    /// Create a new directory only the current user can access. The shared temporary
+    /// directory is writable by everyone, so a name someone else got to first is never
+    /// reused.
+    pub fn create() -> io::Result<Self> {
+        let mut attempt = 0;
+        loop {
+            let directory = std::env::temp_dir().join(format!(
+                "mici-{}-{:016x}",
+                std::process::id(),
+                RandomState::new().hash_one(attempt)
+            ));
+
+            match private_dir_builder().create(&directory) {
+                Ok(()) => {
+                    return Ok(Self {
+                        directory,
+                        counter: AtomicUsize::new(0),
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < CREATE_ATTEMPTS => {
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Create empty files for the next execution of `step_id`.
+    pub fn step_files(&self, step_id: &str) -> io::Result<StepFiles> {
+        let number = self.counter.fetch_add(1, Ordering::SeqCst);
+        let file = |extension: &str| -> io::Result<PathBuf> {
+            let path = self.directory.join(format!(
+                "{}-{}.{}",
+                number,
+                file_name_safe(step_id),
+                extension
+            ));
+            private_file_options().open(&path)?;
+            Ok(path)
+        };
+
+        Ok(StepFiles {
+            name: step_id.to_string(),
+            output: file("output")?,
+            env: file("env")?,
+            path: file("path")?,
+        })
+    }
+}
+
+impl StepFiles {
+    /// Empty all files again, e.g. before a step is retried.
+    pub fn reset(&self) -> io::Result<()> {
+        for path in [&self.output, &self.env, &self.path] {
+            fs::File::create(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Step ids come from the command file and may hold characters that aren't safe in
+/// file names, e.g. `/`.
+fn file_name_safe(step_id: &str) -> String {
+    step_id
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn private_dir_builder() -> fs::DirBuilder {
+    #[allow(unused_mut)]
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder
+}
+
+/// Files steps write outputs and environment variables to, which may hold secrets.
+fn private_file_options() -> fs::OpenOptions {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+impl Drop for RunFiles {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.directory) {
+            tracing::debug!(
+                "Failed to remove temporary directory {}: {}",
+                self.directory.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Read a `key=value` file written by a step.
+///
+/// Multiline values use a heredoc-style delimiter:
+/// ```text
+/// changelog<<EOF
+/// first line
+/// second line
+/// EOF
+/// ```
+/// Malformed lines are skipped with a warning.
+pub fn read_key_value_file(path: &Path) -> BTreeMap<String, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::warn!("Failed to read {}: {}", path.display(), e);
+            return BTreeMap::new();
+        }
+    };
+
+    parse_key_values(path, &content)
+}
+
+/// Read a file with one entry per line, e.g. directories written to `MICI_PATH`.
+pub fn read_lines_file(path: &Path) -> Vec<String> {
+    match fs::read_to_string(path) {
+        Ok(content) => content
+            .trim_start_matches('\u{feff}')
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(e) => {
+            tracing::warn!("Failed to read {}: {}", path.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+fn parse_key_values(path: &Path, content: &str) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    // PowerShell may write a byte order mark
+    let mut lines = content.trim_start_matches('\u{feff}').lines().enumerate();
+
+    while let Some((index, line)) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let heredoc = line.split_once("<<");
+        let assignment = line.split_once('=');
+
+        // `key<<DELIM` unless the `=` comes first, e.g. `key=a<<b`
+        match (heredoc, assignment) {
+            (Some((key, delimiter)), assignment)
+                if !key.is_empty() && assignment.is_none_or(|(k, _)| k.len() > key.len()) =>
+            {
+                let mut value: Vec<&str> = Vec::new();
+                let mut terminated = false;
+
+                for (_, line) in lines.by_ref() {
+                    if line == delimiter {
+                        terminated = true;
+                        break;
+                    }
+                    value.push(line);
+                }
+
+                if !terminated {
+                    tracing::warn!(
+                        "Missing closing delimiter '{}' for '{}', using the rest of the file",
+                        delimiter,
+                        key
+                    );
+                }
+
+                values.insert(key.to_string(), value.join("\n"));
+            }
+            (_, Some((key, value))) if !key.is_empty() => {
+                values.insert(key.to_string(), value.to_string());
+            }
+            _ => {
+                // The line itself may hold a secret
+                tracing::warn!(
+                    "Ignoring malformed line {} in {}, expected key=value",
+                    index + 1,
+                    path.display()
+                );
+            }
+        }
+    }
+
+    values
+}
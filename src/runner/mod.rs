@@ -1,2 +1,9 @@
 pub mod context;
 pub mod coordinator;
+pub mod files;
+pub mod output;
+pub mod process;
+pub mod record;
+pub mod secrets;
+pub mod signals;
+pub mod state;
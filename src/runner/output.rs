@@ -0,0 +1,294 @@
+use crate::runner::{process, record::StepLogs, secrets::Secrets, signals};
+use colored::Colorize;
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, ExitStatus},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often the output loop checks for cancellation while waiting for new lines.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long to keep draining output after a cancelled step was killed.
+/// Processes spawned by the step may hold on to the pipes after it exits.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long a step gets to exit after SIGTERM (or the signal mici received) before it is killed.
+const TERMINATION_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Output captured from a step while it was being streamed to the terminal.
+#[derive(Debug, Default, Clone)]
+pub struct CapturedOutput {
+    pub stdout: String,
+    pub stderr: String,
+}
+
+pub struct StreamOptions<'a> {
+    /// Printed before every line, e.g. for steps running in parallel
+    pub prefix: Option<&'a str>,
+    /// Setting this terminates the child
+    pub cancel: &'a AtomicBool,
+    /// The child is terminated once this passes
+    pub deadline: Option<Instant>,
+    /// Whether the child leads its own process group, which is then terminated as a whole
+    pub process_group: bool,
+    /// Masked in everything that is written out or captured
+    pub secrets: &'a Secrets,
+    /// Files the output is written to as well, for the run's record
+    pub logs: Option<&'a StepLogs>,
+}
+
+pub struct StreamedChild {
+    pub status: ExitStatus,
+    pub output: CapturedOutput,
+    /// Whether the child was terminated because its deadline passed
+    pub timed_out: bool,
+}
+
+enum Termination {
+    Running,
+    /// SIGTERM (or the signal mici received) was sent, the child is killed if it's still running at `kill_at`
+    Terminating {
+        kill_at: Instant,
+    },
+    /// The child was killed, output is drained until `drain_until`
+    Killed {
+        drain_until: Instant,
+    },
+}
+
+/// Stream the child's stdout/stderr as it is produced, while also capturing it.
+/// Secrets are masked in both, also when one is split across lines or reads.
+///
+/// Both pipes are read on their own thread and funneled through a single channel,
+/// so output is written out in the order it arrives, regardless of which stream it came from.
+/// Prefixed output is written line by line, everything else as soon as it's read, so a
+/// partial line (e.g. a progress bar) shows up without waiting for its newline.
+/// Once cancelled or past its deadline, the child is asked to stop and gets
+/// `TERMINATION_GRACE_PERIOD` to do so before it's killed.
+pub fn stream_child_output(
+    child: &mut Child,
+    options: &StreamOptions,
+) -> std::io::Result<StreamedChild> {
+    let (sender, receiver) = mpsc::channel::<(OutputStream, Vec<u8>)>();
+
+    let line_buffered = options.prefix.is_some();
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(spawn_reader(
+            stdout,
+            OutputStream::Stdout,
+            line_buffered,
+            sender.clone(),
+        ));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(spawn_reader(
+            stderr,
+            OutputStream::Stderr,
+            line_buffered,
+            sender.clone(),
+        ));
+    }
+    drop(sender);
+
+    let prefix = options
+        .prefix
+        .map(|p| format!("{} ", format!("[{}]", p).bright_black()));
+
+    let mut captured_stdout: Vec<u8> = Vec::new();
+    let mut captured_stderr: Vec<u8> = Vec::new();
+    let mut stdout_masker = options.secrets.masker();
+    let mut stderr_masker = options.secrets.masker();
+    let mut termination = Termination::Running;
+    let mut timed_out = false;
+    // Interactive steps write to the terminal directly, there's nothing to read
+    let unpiped = readers.is_empty();
+
+    loop {
+        if unpiped {
+            if matches!(child.try_wait(), Ok(Some(_))) {
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        } else {
+            match receiver.recv_timeout(POLL_INTERVAL) {
+                Ok((stream, chunk)) => {
+                    let chunk = match stream {
+                        OutputStream::Stdout => stdout_masker.push(&chunk),
+                        OutputStream::Stderr => stderr_masker.push(&chunk),
+                    };
+                    write_output(
+                        stream,
+                        &chunk,
+                        prefix.as_deref(),
+                        options.logs,
+                        &mut captured_stdout,
+                        &mut captured_stderr,
+                    )?;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        let now = Instant::now();
+        match termination {
+            Termination::Running => {
+                let cancelled = options.cancel.load(Ordering::SeqCst);
+                timed_out = !cancelled && options.deadline.is_some_and(|d| now >= d);
+
+                if cancelled || timed_out {
+                    // A step cancelled because mici was interrupted gets the same signal
+                    match signals::latest().filter(|_| cancelled) {
+                        Some(signal) => process::forward(child, options.process_group, signal),
+                        None => process::terminate(child, options.process_group),
+                    }
+                    termination = Termination::Terminating {
+                        kill_at: now + TERMINATION_GRACE_PERIOD,
+                    };
+                }
+            }
+            Termination::Terminating { kill_at } => {
+                let exited = matches!(child.try_wait(), Ok(Some(_)));
+                if exited || now >= kill_at {
+                    process::kill(child, options.process_group);
+                    termination = Termination::Killed {
+                        drain_until: now + DRAIN_TIMEOUT,
+                    };
+                }
+            }
+            Termination::Killed { drain_until } if now >= drain_until => break,
+            Termination::Killed { .. } => {}
+        }
+    }
+
+    if matches!(termination, Termination::Running) {
+        for reader in readers {
+            let _ = reader.join();
+        }
+    }
+
+    // Whatever was held back as the possible start of a secret
+    for (stream, masker) in [
+        (OutputStream::Stdout, &mut stdout_masker),
+        (OutputStream::Stderr, &mut stderr_masker),
+    ] {
+        let rest = masker.finish();
+        write_output(
+            stream,
+            &rest,
+            prefix.as_deref(),
+            options.logs,
+            &mut captured_stdout,
+            &mut captured_stderr,
+        )?;
+    }
+
+    let status = child.wait()?;
+
+    Ok(StreamedChild {
+        status,
+        output: CapturedOutput {
+            stdout: String::from_utf8_lossy(&captured_stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&captured_stderr).into_owned(),
+        },
+        timed_out,
+    })
+}
+
+/// Capture `output` and write it to mici's own stdout or stderr, and to the step's log file.
+fn write_output(
+    stream: OutputStream,
+    output: &[u8],
+    prefix: Option<&str>,
+    logs: Option<&StepLogs>,
+    captured_stdout: &mut Vec<u8>,
+    captured_stderr: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    if output.is_empty() {
+        return Ok(());
+    }
+
+    let log = logs.map(|logs| match stream {
+        OutputStream::Stdout => &logs.stdout,
+        OutputStream::Stderr => &logs.stderr,
+    });
+    // A full disk shouldn't fail the step, the terminal still gets its output
+    if let Some(mut log) = log
+        && let Err(e) = log.write_all(output)
+    {
+        tracing::debug!("Failed to write step output to its log file: {}", e);
+    }
+
+    match stream {
+        OutputStream::Stdout => {
+            captured_stdout.extend_from_slice(output);
+            write_line(&mut std::io::stdout().lock(), prefix, output)
+        }
+        OutputStream::Stderr => {
+            captured_stderr.extend_from_slice(output);
+            write_line(&mut std::io::stderr().lock(), prefix, output)
+        }
+    }
+}
+
+fn write_line(out: &mut dyn Write, prefix: Option<&str>, line: &[u8]) -> std::io::Result<()> {
+    if let Some(prefix) = prefix {
+        out.write_all(prefix.as_bytes())?;
+        out.write_all(line)?;
+        // Keep prefixed lines from running into each other
+        if !line.ends_with(b"\n") {
+            out.write_all(b"\n")?;
+        }
+    } else {
+        out.write_all(line)?;
+    }
+    out.flush()
+}
+
+/// Read `source` until it's closed, sending whole lines when `line_buffered` and whatever
+/// could be read otherwise.
+fn spawn_reader<R: Read + Send + 'static>(
+    source: R,
+    stream: OutputStream,
+    line_buffered: bool,
+    sender: mpsc::Sender<(OutputStream, Vec<u8>)>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(source);
+        loop {
+            let chunk = if line_buffered {
+                let mut line = Vec::new();
+                reader.read_until(b'\n', &mut line).map(|_| line)
+            } else {
+                reader.fill_buf().map(|buffer| buffer.to_vec())
+            };
+
+            match chunk {
+                Ok(chunk) if chunk.is_empty() => break,
+                Ok(chunk) => {
+                    if !line_buffered {
+                        reader.consume(chunk.len());
+                    }
+                    if sender.send((stream, chunk)).is_err() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+    })
+}
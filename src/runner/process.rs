@@ -0,0 +1,89 @@
+use std::process::Child;
+
+/// Ask the child to stop: SIGTERM on unix, sent to its whole process group when it leads one.
+/// Windows has no equivalent, so the child is killed right away.
+pub fn terminate(child: &mut Child, process_group: bool) {
+    #[cfg(unix)]
+    {
+        signal(child, process_group, libc::SIGTERM);
+    }
+    #[cfg(windows)]
+    {
+        let _ = process_group;
+        let _ = child.kill();
+    }
+}
+
+/// Pass a signal mici received on to the child, or its whole process group when it leads one.
+/// Windows has no equivalent, so the child is killed right away.
+pub fn forward(child: &mut Child, process_group: bool, signal: i32) {
+    #[cfg(unix)]
+    {
+        self::signal(child, process_group, signal);
+    }
+    #[cfg(windows)]
+    {
+        let _ = (process_group, signal);
+        let _ = child.kill();
+    }
+}
+
+/// Kill the child, together with its process group when it leads one.
+pub fn kill(child: &mut Child, process_group: bool) {
+    #[cfg(unix)]
+    {
+        signal(child, process_group, libc::SIGKILL);
+    }
+    #[cfg(windows)]
+    {
+        let _ = process_group;
+        let _ = child.kill();
+    }
+}
+
+/// Whether a process with this pid exists, e.g. the mici process of a run that is
+/// recorded as running. Assumes it does when that can't be told.
+pub fn exists(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return true;
+        };
+        // SAFETY: signal 0 only checks whether the process exists, without memory access
+        let result = unsafe { libc::kill(pid, 0) };
+        result == 0 || std::io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+    }
+    #[cfg(windows)]
+    {
+        let output = std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+            .output();
+        // Lists the process as `"mici.exe","<pid>",...` if it exists
+        let Ok(output) = output else {
+            return true;
+        };
+        String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\",", pid))
+    }
+}
+
+#[cfg(unix)]
+fn signal(child: &mut Child, process_group: bool, signal: libc::c_int) {
+    // Once reaped, the child's pid may be reused. Its process group lives on
+    // as long as any of its members does, so that one is still signalled.
+    if !process_group && matches!(child.try_wait(), Ok(Some(_))) {
+        return;
+    }
+
+    let Ok(pid) = libc::pid_t::try_from(child.id()) else {
+        return;
+    };
+
+    // SAFETY: plain syscalls without memory access, failures (e.g. ESRCH) are ignored
+    unsafe {
+        if process_group {
+            libc::killpg(pid, signal);
+        } else {
+            libc::kill(pid, signal);
+        }
+    }
+}
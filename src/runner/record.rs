@@ -0,0 +1,260 @@
+use crate::{
+    runner::{process, state::StepResult},
+    utils::{
+        fs::get_runs_folder,
+        timestamp::{format_utc, format_utc_compact},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+/// Name of the file holding a run's `RunSummary`.
+pub const RUN_FILE: &str = "run.yml";
+/// Name of the file holding the command a run was started from, with references to
+/// inputs resolved and secrets masked.
+pub const COMMAND_FILE: &str = "command.yml";
+/// Name of the file holding a run's inputs, with secrets masked.
+pub const INPUTS_FILE: &str = "inputs.yml";
+/// Directory of a run holding the stdout and stderr of every step.
+pub const STEPS_FOLDER: &str = "steps";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Success,
+    Failure,
+    Interrupted,
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunStatus::Running => write!(f, "running"),
+            RunStatus::Success => write!(f, "success"),
+            RunStatus::Failure => write!(f, "failure"),
+            RunStatus::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+/// What `run.yml` of a run holds, updated as its steps finish.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub id: String,
+    /// Path of the command, e.g. `db/restore`
+    pub command: String,
+    /// Who started the run
+    pub user: Option<String>,
+    /// Of the mici process running it
+    #[serde(default)]
+    pub pid: Option<u32>,
+    pub status: RunStatus,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub steps: Vec<StepSummary>,
+}
+
+impl RunSummary {
+    /// A run whose mici process is gone without having finished it, e.g. because it was
+    /// killed with SIGKILL, is over even though it's still recorded as running.
+    fn mark_abandoned(&mut self) {
+        if self.status == RunStatus::Running && self.pid.is_some_and(|pid| !process::exists(pid)) {
+            self.status = RunStatus::Interrupted;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepSummary {
+    pub id: String,
+    pub outcome: String,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub attempts: u32,
+    pub started_at: String,
+    /// How long the last attempt ran
+    pub duration_ms: u64,
+}
+
+/// Files a step's output is written to while it runs, next to the terminal.
+pub struct StepLogs {
+    pub stdout: fs::File,
+    pub stderr: fs::File,
+}
+
+/// The directory of a single run, `~/.mici/runs/<command>/<run-id>/`, kept after the run
+/// to look into it later.
+pub struct RunRecord {
+    directory: PathBuf,
+    started: SystemTime,
+    summary: Mutex<RunSummary>,
+}
+
+impl RunRecord {
+    /// Create the directory of a new run of `command`, with a `snapshot` of the command it
+    /// runs and its `inputs`, which must both already be masked.
+    pub fn create(
+        command: &str,
+        snapshot: &str,
+        inputs: &BTreeMap<String, String>,
+    ) -> io::Result<Self> {
+        let now = SystemTime::now();
+        let pid = std::process::id();
+        let id = format!("{}-{}", format_utc_compact(now), pid);
+        let directory = get_runs_folder().join(command).join(&id);
+        fs::create_dir_all(directory.join(STEPS_FOLDER))?;
+
+        fs::write(directory.join(COMMAND_FILE), snapshot)?;
+        fs::write(directory.join(INPUTS_FILE), to_yaml(inputs)?)?;
+
+        let record = Self {
+            directory,
+            started: now,
+            summary: Mutex::new(RunSummary {
+                id,
+                command: command.to_string(),
+                user: std::env::var("USER")
+                    .or_else(|_| std::env::var("USERNAME"))
+                    .ok(),
+                pid: Some(pid),
+                status: RunStatus::Running,
+                started_at: format_utc(now),
+                finished_at: None,
+                duration_ms: None,
+                exit_code: None,
+                steps: Vec::new(),
+            }),
+        };
+        record.save(&record.summary.lock().unwrap())?;
+
+        Ok(record)
+    }
+
+    pub fn id(&self) -> String {
+        self.summary.lock().unwrap().id.clone()
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Create the files the output of `step_id` is written to. A step that is retried
+    /// keeps writing to the same files.
+    pub fn step_logs(&self, step_id: &str) -> io::Result<StepLogs> {
+        let (stdout, stderr) = step_log_files(&self.directory, step_id);
+        Ok(StepLogs {
+            stdout: fs::File::create(stdout)?,
+            stderr: fs::File::create(stderr)?,
+        })
+    }
+
+    /// Add a finished step, which was started at `started`.
+    pub fn finish_step(&self, step_id: &str, started: SystemTime, result: &StepResult) {
+        let mut summary = self.summary.lock().unwrap();
+        summary.steps.push(StepSummary {
+            id: step_id.to_string(),
+            outcome: result.outcome.to_string(),
+            exit_code: result.exit_code,
+            signal: result.signal,
+            attempts: result.attempts,
+            started_at: format_utc(started),
+            duration_ms: result.elapsed.as_millis() as u64,
+        });
+        self.save_or_warn(&summary);
+    }
+
+    /// Mark the run as over, with the exit code mici exits with.
+    pub fn finish(&self, status: RunStatus, exit_code: i32) {
+        let mut summary = self.summary.lock().unwrap();
+        let now = SystemTime::now();
+
+        summary.status = status;
+        summary.exit_code = Some(exit_code);
+        summary.finished_at = Some(format_utc(now));
+        summary.duration_ms = now
+            .duration_since(self.started)
+            .ok()
+            .map(|d| d.as_millis() as u64);
+        self.save_or_warn(&summary);
+    }
+
+    fn save_or_warn(&self, summary: &RunSummary) {
+        if let Err(e) = self.save(summary) {
+            tracing::warn!("Failed to update the record of run {}: {}", summary.id, e);
+        }
+    }
+
+    /// Write `run.yml` through a temporary file, so readers never see half of it.
+    fn save(&self, summary: &RunSummary) -> io::Result<()> {
+        let temporary = self.directory.join(format!("{}.tmp", RUN_FILE));
+        fs::write(&temporary, to_yaml(summary)?)?;
+        fs::rename(temporary, self.directory.join(RUN_FILE))
+    }
+}
+
+/// Every recorded run with its directory, most recent first.
+pub fn read_runs() -> Vec<(PathBuf, RunSummary)> {
+    let mut runs = Vec::new();
+    collect_runs(&get_runs_folder(), &mut runs);
+    runs.sort_by(|(_, a), (_, b)| b.started_at.cmp(&a.started_at).then(b.id.cmp(&a.id)));
+    runs
+}
+
+/// Run directories sit below folders named after the path of their command, which may
+/// be nested, e.g. `runs/db/restore/<run-id>/`.
+fn collect_runs(folder: &Path, runs: &mut Vec<(PathBuf, RunSummary)>) {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        match read_run(&path) {
+            Some(summary) => runs.push((path, summary)),
+            None => collect_runs(&path, runs),
+        }
+    }
+}
+
+/// The `run.yml` of the run at `directory`, if it is one. A run whose mici process is
+/// gone is reported as interrupted.
+pub fn read_run(directory: &Path) -> Option<RunSummary> {
+    let content = fs::read_to_string(directory.join(RUN_FILE)).ok()?;
+    match serde_yaml::from_str::<RunSummary>(&content) {
+        Ok(mut summary) => {
+            summary.mark_abandoned();
+            Some(summary)
+        }
+        Err(e) => {
+            tracing::warn!("Ignoring run at {}: {}", directory.display(), e);
+            None
+        }
+    }
+}
+
+/// The stdout and stderr log files of `step_id` in the run at `directory`.
+pub fn step_log_files(directory: &Path, step_id: &str) -> (PathBuf, PathBuf) {
+    let name = step_id.replace(['/', '\\'], "_");
+    let steps = directory.join(STEPS_FOLDER);
+    (
+        steps.join(format!("{}.stdout.log", name)),
+        steps.join(format!("{}.stderr.log", name)),
+    )
+}
+
+fn to_yaml<T: Serialize>(value: &T) -> io::Result<String> {
+    serde_yaml::to_string(value).map_err(io::Error::other)
+}
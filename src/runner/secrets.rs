@@ -0,0 +1,106 @@
+use crate::utils::resolver::SECRET_MASK;
+
+/// Values that must never show up in anything mici prints or keeps: secret inputs
+/// and the variables listed in `secret_environment`.
+#[derive(Debug, Default, Clone)]
+pub struct Secrets {
+    /// Longest first, so a secret containing another one is masked as a whole
+    values: Vec<String>,
+}
+
+impl Secrets {
+    pub fn new(values: impl IntoIterator<Item = String>) -> Self {
+        let mut secrets = Self::default();
+        secrets.extend(values);
+        secrets
+    }
+
+    pub fn extend(&mut self, values: impl IntoIterator<Item = String>) {
+        self.values
+            .extend(values.into_iter().filter(|value| !value.is_empty()));
+        self.values
+            .sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        self.values.dedup();
+    }
+
+    /// Replace every secret in `text` with `SECRET_MASK`.
+    pub fn mask(&self, text: &str) -> String {
+        let mut masker = self.masker();
+        let masked = masker.finish_with(text.as_bytes());
+        String::from_utf8_lossy(&masked).into_owned()
+    }
+
+    /// A masker for output that arrives in chunks, e.g. the lines of a step's stdout.
+    pub fn masker(&self) -> SecretMasker<'_> {
+        SecretMasker {
+            secrets: self,
+            pending: Vec::new(),
+        }
+    }
+}
+
+/// Masks secrets in a stream of chunks. A chunk ending in what might be the start of a
+/// secret is held back until the next chunk tells whether it is one.
+pub struct SecretMasker<'a> {
+    secrets: &'a Secrets,
+    pending: Vec<u8>,
+}
+
+impl SecretMasker<'_> {
+    /// Add a chunk, returning the masked output that can be written out so far.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(chunk);
+        self.mask_pending(false)
+    }
+
+    /// The rest of the output, once the stream has ended.
+    pub fn finish(&mut self) -> Vec<u8> {
+        self.mask_pending(true)
+    }
+
+    fn finish_with(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(chunk);
+        self.finish()
+    }
+
+    fn mask_pending(&mut self, finished: bool) -> Vec<u8> {
+        let pending = std::mem::take(&mut self.pending);
+        if self.secrets.values.is_empty() {
+            return pending;
+        }
+        let mut masked = Vec::with_capacity(pending.len());
+
+        let mut position = 0;
+        while position < pending.len() {
+            let rest = &pending[position..];
+
+            // The rest could still turn out to be (a longer) secret once more output arrives
+            if !finished
+                && self
+                    .secrets
+                    .values
+                    .iter()
+                    .any(|secret| secret.len() > rest.len() && secret.as_bytes().starts_with(rest))
+            {
+                self.pending = rest.to_vec();
+                break;
+            }
+
+            if let Some(secret) = self
+                .secrets
+                .values
+                .iter()
+                .find(|secret| rest.starts_with(secret.as_bytes()))
+            {
+                masked.extend_from_slice(SECRET_MASK.as_bytes());
+                position += secret.len();
+                continue;
+            }
+
+            masked.push(pending[position]);
+            position += 1;
+        }
+
+        masked
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+
+/// The first signal mici received, 0 if none
+static RECEIVED: AtomicI32 = AtomicI32::new(0);
+/// The last signal mici received, 0 if none
+static LATEST: AtomicI32 = AtomicI32::new(0);
+/// How many signals mici received
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Catch SIGINT, SIGTERM and SIGHUP, so running steps can be stopped and `cleanup:` steps
+/// still run before mici exits. Check for them with `received()`, and for any that
+/// arrive later with `count()` and `latest()`.
+pub fn install_signal_handlers() {
+    #[cfg(unix)]
+    {
+        extern "C" fn handle(signal: libc::c_int) {
+            let _ = RECEIVED.compare_exchange(0, signal, Ordering::SeqCst, Ordering::SeqCst);
+            LATEST.store(signal, Ordering::SeqCst);
+            COUNT.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let handler: extern "C" fn(libc::c_int) = handle;
+
+        // SAFETY: the handler only updates atomics, which is async-signal-safe
+        unsafe {
+            for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+                libc::signal(signal, handler as libc::sighandler_t);
+            }
+        }
+    }
+}
+
+/// The signal that interrupted mici, if any.
+pub fn received() -> Option<i32> {
+    match RECEIVED.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+/// The signal mici received last, if any.
+pub fn latest() -> Option<i32> {
+    match LATEST.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+/// How many signals mici received so far.
+pub fn count() -> usize {
+    COUNT.load(Ordering::SeqCst)
+}
+
+/// Name of a signal for messages, e.g. `SIGINT`.
+pub fn name(signal: i32) -> String {
+    #[cfg(unix)]
+    {
+        let name = match signal {
+            libc::SIGHUP => Some("SIGHUP"),
+            libc::SIGINT => Some("SIGINT"),
+            libc::SIGQUIT => Some("SIGQUIT"),
+            libc::SIGABRT => Some("SIGABRT"),
+            libc::SIGKILL => Some("SIGKILL"),
+            libc::SIGSEGV => Some("SIGSEGV"),
+            libc::SIGPIPE => Some("SIGPIPE"),
+            libc::SIGTERM => Some("SIGTERM"),
+            _ => None,
+        };
+        if let Some(name) = name {
+            return name.to_string();
+        }
+    }
+
+    format!("signal {}", signal)
+}
+
+/// How a step's process ended, for messages: `failed with exit code: 2` or `was killed by SIGKILL`.
+pub fn describe_exit(exit_code: i32, signal: Option<i32>) -> String {
+    match signal {
+        Some(signal) => format!("was killed by {}", name(signal)),
+        None => format!("failed with exit code: {}", exit_code),
+    }
+}
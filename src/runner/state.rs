@@ -0,0 +1,139 @@
+use crate::runner::{output::CapturedOutput, signals};
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+/// Where a step is in its lifecycle while the coordinator schedules it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepState {
+    Pending,
+    Running,
+    Finished(StepOutcome),
+}
+
+impl StepState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, StepState::Finished(_))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepOutcome {
+    Success,
+    Failure,
+    Cancelled,
+    Skipped,
+}
+
+impl fmt::Display for StepOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepOutcome::Success => write!(f, "success"),
+            StepOutcome::Failure => write!(f, "failure"),
+            StepOutcome::Cancelled => write!(f, "cancelled"),
+            StepOutcome::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// What a step left behind once it finished.
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    pub outcome: StepOutcome,
+    pub exit_code: Option<i32>,
+    /// The signal that killed the step's process, if it didn't exit on its own
+    pub signal: Option<i32>,
+    pub output: CapturedOutput,
+    /// Values the step wrote to its `MICI_OUTPUT` file
+    pub outputs: BTreeMap<String, String>,
+    /// Variables the step wrote to its `MICI_ENV` file
+    pub environment: BTreeMap<String, String>,
+    /// Directories the step wrote to its `MICI_PATH` file
+    pub path: Vec<String>,
+    /// How many times the step was run, including retries
+    pub attempts: u32,
+    /// Whether the last attempt was terminated because of a timeout
+    pub timed_out: bool,
+    /// How long the last attempt ran
+    pub elapsed: Duration,
+}
+
+impl StepResult {
+    pub fn skipped() -> Self {
+        Self {
+            outcome: StepOutcome::Skipped,
+            exit_code: None,
+            signal: None,
+            output: CapturedOutput::default(),
+            outputs: BTreeMap::new(),
+            environment: BTreeMap::new(),
+            path: Vec::new(),
+            attempts: 0,
+            timed_out: false,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// The exit code mici reports for the step: its own, or 128 + signal as shells do
+    /// when it was killed.
+    pub fn reported_exit_code(&self) -> i32 {
+        match self.signal {
+            Some(signal) => 128 + signal,
+            None => self.exit_code.unwrap_or(1),
+        }
+    }
+
+    /// How the step's process ended, e.g. `failed with exit code: 2`.
+    pub fn describe_exit(&self) -> String {
+        signals::describe_exit(self.reported_exit_code(), self.signal)
+    }
+
+    /// Variables exposed to later steps as `@{steps.<id>.*}`.
+    pub fn variables(&self, step_id: &str) -> Vec<(String, String)> {
+        let mut variables = vec![
+            (
+                format!("steps.{}.output", step_id),
+                self.output.stdout.trim().to_string(),
+            ),
+            (
+                format!("steps.{}.exit_code", step_id),
+                self.exit_code.map(|c| c.to_string()).unwrap_or_default(),
+            ),
+            (
+                format!("steps.{}.outcome", step_id),
+                self.outcome.to_string(),
+            ),
+        ];
+
+        for (key, value) in &self.outputs {
+            variables.push((format!("steps.{}.outputs.{}", step_id, key), value.clone()));
+        }
+
+        variables
+    }
+}
+
+/// Everything finished steps hand over to the steps started after them.
+#[derive(Debug, Default, Clone)]
+pub struct Exports {
+    /// `@{steps.<id>.*}` values
+    pub variables: BTreeMap<String, String>,
+    /// Variables from `MICI_ENV` files
+    pub environment: BTreeMap<String, String>,
+    /// Directories from `MICI_PATH` files, most recently added first
+    pub path: Vec<String>,
+    /// Full stdout of finished steps, for `stdin: { step: <id> }`
+    pub stdout: BTreeMap<String, String>,
+}
+
+impl Exports {
+    pub fn record(&mut self, step_id: &str, result: &StepResult) {
+        self.variables.extend(result.variables(step_id));
+        self.environment.extend(result.environment.clone());
+        self.stdout
+            .insert(step_id.to_string(), result.output.stdout.clone());
+
+        for directory in &result.path {
+            self.path.retain(|d| d != directory);
+            self.path.insert(0, directory.clone());
+        }
+    }
+}
@@ -1,6 +1,11 @@
 pub mod checks;
+pub mod dotenv;
+pub mod duration;
+pub mod expression;
 pub mod fs;
+pub mod pattern;
 pub mod print;
 pub mod resolver;
+pub mod timestamp;
 pub mod traits;
 pub mod yaml;
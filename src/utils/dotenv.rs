@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+/// Parse a dotenv file: `KEY=value` lines, optionally prefixed with `export`.
+///
+/// Blank lines and lines starting with `#` are skipped. Values in double quotes may use
+/// `\n`, `\t`, `\"` and `\\`, values in single quotes are taken as written, and unquoted
+/// values end at ` #`. Malformed lines are reported and skipped.
+pub fn parse_dotenv(content: &str) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+
+    for (index, line) in content.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            tracing::warn!(
+                "Ignoring malformed line {} in env file, expected KEY=value",
+                index + 1
+            );
+            continue;
+        };
+
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            tracing::warn!(
+                "Ignoring malformed line {} in env file, expected KEY=value",
+                index + 1
+            );
+            continue;
+        }
+
+        values.insert(key.to_string(), parse_value(value.trim()));
+    }
+
+    values
+}
+
+fn parse_value(value: &str) -> String {
+    if let Some(quoted) = value.strip_prefix('"')
+        && let Some(end) = closing_quote(quoted)
+    {
+        let mut unescaped = String::with_capacity(end);
+        let mut chars = quoted[..end].chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('t') => unescaped.push('\t'),
+                Some(other) => unescaped.push(other),
+                None => unescaped.push('\\'),
+            }
+        }
+        return unescaped;
+    }
+
+    if let Some(quoted) = value.strip_prefix('\'')
+        && let Some(end) = quoted.find('\'')
+    {
+        return quoted[..end].to_string();
+    }
+
+    match value.find(" #") {
+        Some(comment) => value[..comment].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// Byte offset of the first unescaped `"` in `text`.
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (offset, c) in text.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(offset),
+            _ => escaped = false,
+        }
+    }
+    None
+}
@@ -0,0 +1,90 @@
+//! Human-friendly durations used in command files, e.g. `delay: "5s"` or `timeout: "1h30m"`.
+
+use std::time::Duration;
+
+/// The longest duration accepted, far beyond any timeout or delay that makes sense, so
+/// that adding it to the current time can't overflow.
+pub const MAX_DURATION: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+/// Parse a duration made of one or more `<number><unit>` parts.
+/// Supported units are `ms`, `s`, `m` and `h`. A plain number is read as seconds.
+/// Whitespace is ignored, so `"1h 30m"` works too. Durations above `MAX_DURATION` are
+/// rejected.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let compact: String = text.split_whitespace().collect();
+    let text = text.trim();
+    if compact.is_empty() {
+        return Err("duration is empty".to_string());
+    }
+
+    let too_long = || format!("duration is too long in '{}', at most 100 years", text);
+
+    if let Ok(seconds) = compact.parse::<u64>() {
+        return Some(Duration::from_secs(seconds))
+            .filter(|duration| *duration <= MAX_DURATION)
+            .ok_or_else(too_long);
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = compact.as_str();
+
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(format!("expected a number in '{}'", text));
+        }
+
+        let value: u64 = rest[..digits]
+            .parse()
+            .map_err(|_| format!("number is too large in '{}'", text))?;
+        rest = &rest[digits..];
+
+        let unit_length = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let part = match &rest[..unit_length] {
+            "ms" => Duration::from_millis(value),
+            "s" => Duration::from_secs(value),
+            "m" => Duration::from_secs(value.saturating_mul(60)),
+            "h" => Duration::from_secs(value.saturating_mul(60 * 60)),
+            "" => return Err(format!("missing unit in '{}'", text)),
+            unit => {
+                return Err(format!(
+                    "unknown unit '{}' in '{}', expected ms, s, m or h",
+                    unit, text
+                ));
+            }
+        };
+
+        total = total
+            .checked_add(part)
+            .filter(|total| *total <= MAX_DURATION)
+            .ok_or_else(too_long)?;
+        rest = &rest[unit_length..];
+    }
+
+    Ok(total)
+}
+
+/// Format a duration for log messages, e.g. `1m 5.2s` or `350ms`.
+pub fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+
+    if millis < 1000 {
+        return format!("{}ms", millis);
+    }
+
+    let seconds = duration.as_secs_f64() % 60.0;
+    let minutes = (duration.as_secs() / 60) % 60;
+    let hours = duration.as_secs() / 3600;
+
+    if hours > 0 {
+        format!("{}h {}m {:.0}s", hours, minutes, seconds.floor())
+    } else if minutes > 0 {
+        format!("{}m {:.1}s", minutes, seconds)
+    } else {
+        format!("{:.1}s", seconds)
+    }
+}
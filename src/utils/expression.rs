@@ -0,0 +1,667 @@
+//! Expression language used by `when:` conditions on steps.
+//!
+//! Supported syntax:
+//!   - Input references:     `@{inputs.branch}`
+//!   - Step results:         `@{steps.build.outcome}`
+//!   - Environment lookups:  `${DEPLOY_ENV}`
+//!   - Literals:             `"main"`, `'main'`, `true`, `false`, `42`
+//!   - Comparisons:          `==`, `!=`
+//!   - Boolean operators:    `&&`, `||`, `!` and parentheses
+//!   - Built-in functions:   `on_success()`, `on_failure()`, `always()`, `on_platform("linux")`
+
+use std::fmt;
+
+/// A value produced while evaluating an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    String(String),
+}
+
+impl Value {
+    /// Strings are truthy unless they are empty, `"false"` or `"0"`.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::String(s) => {
+                let s = s.trim();
+                !(s.is_empty() || s.eq_ignore_ascii_case("false") || s == "0")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// A syntax error with the byte offset and length of the offending part of the expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpressionError {
+    pub message: String,
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl ExpressionError {
+    fn new(message: impl Into<String>, offset: usize, length: usize) -> Self {
+        Self {
+            message: message.into(),
+            offset,
+            length: length.max(1),
+        }
+    }
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at position {})", self.message, self.offset + 1)
+    }
+}
+
+/// Built-in functions callable from expressions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Function {
+    OnSuccess,
+    OnFailure,
+    Always,
+    OnPlatform,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "on_success" => Some(Function::OnSuccess),
+            "on_failure" => Some(Function::OnFailure),
+            "always" => Some(Function::Always),
+            "on_platform" => Some(Function::OnPlatform),
+            _ => None,
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Function::OnSuccess | Function::OnFailure | Function::Always => 0,
+            Function::OnPlatform => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Literal(Value),
+    /// `@{...}` reference, e.g. `inputs.branch`
+    Variable(String),
+    /// `${...}` reference, e.g. `DEPLOY_ENV`
+    Environment(String),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Equal(Box<Expression>, Box<Expression>),
+    NotEqual(Box<Expression>, Box<Expression>),
+    Call(Function, Vec<Expression>),
+}
+
+/// Lookups an expression needs from whoever is evaluating it.
+pub trait ExpressionScope {
+    /// Resolve an `@{...}` reference. `None` means the reference is unknown.
+    fn variable(&self, path: &str) -> Option<String>;
+
+    /// Resolve a `${...}` reference. `None` means the variable is not set.
+    fn environment(&self, name: &str) -> Option<String>;
+
+    /// Whether any previous step has failed.
+    fn has_failure(&self) -> bool;
+}
+
+impl Expression {
+    /// Whether the expression calls `on_success()`, `on_failure()` or `always()`,
+    /// i.e. decides for itself whether it applies after a failure.
+    pub fn checks_status(&self) -> bool {
+        match self {
+            Expression::Literal(_) | Expression::Variable(_) | Expression::Environment(_) => false,
+            Expression::Not(inner) => inner.checks_status(),
+            Expression::And(left, right)
+            | Expression::Or(left, right)
+            | Expression::Equal(left, right)
+            | Expression::NotEqual(left, right) => left.checks_status() || right.checks_status(),
+            Expression::Call(function, args) => {
+                matches!(
+                    function,
+                    Function::OnSuccess | Function::OnFailure | Function::Always
+                ) || args.iter().any(Expression::checks_status)
+            }
+        }
+    }
+
+    pub fn evaluate(&self, scope: &dyn ExpressionScope) -> Value {
+        match self {
+            Expression::Literal(value) => value.clone(),
+            Expression::Variable(path) => match scope.variable(path) {
+                Some(value) => Value::String(value),
+                None => {
+                    tracing::warn!(
+                        "Unknown reference '@{{{}}}' in expression, resolving to empty string",
+                        path
+                    );
+                    Value::String(String::new())
+                }
+            },
+            Expression::Environment(name) => match scope.environment(name) {
+                Some(value) => Value::String(value),
+                None => {
+                    tracing::warn!(
+                        "Environment variable '{}' is not set, resolving to empty string",
+                        name
+                    );
+                    Value::String(String::new())
+                }
+            },
+            Expression::Not(inner) => Value::Bool(!inner.evaluate(scope).is_truthy()),
+            Expression::And(left, right) => {
+                Value::Bool(left.evaluate(scope).is_truthy() && right.evaluate(scope).is_truthy())
+            }
+            Expression::Or(left, right) => {
+                Value::Bool(left.evaluate(scope).is_truthy() || right.evaluate(scope).is_truthy())
+            }
+            Expression::Equal(left, right) => {
+                Value::Bool(left.evaluate(scope).to_string() == right.evaluate(scope).to_string())
+            }
+            Expression::NotEqual(left, right) => {
+                Value::Bool(left.evaluate(scope).to_string() != right.evaluate(scope).to_string())
+            }
+            Expression::Call(function, args) => match function {
+                Function::OnSuccess => Value::Bool(!scope.has_failure()),
+                Function::OnFailure => Value::Bool(scope.has_failure()),
+                Function::Always => Value::Bool(true),
+                Function::OnPlatform => {
+                    let platform = args
+                        .first()
+                        .map(|a| a.evaluate(scope).to_string())
+                        .unwrap_or_default();
+                    Value::Bool(is_current_platform(&platform))
+                }
+            },
+        }
+    }
+}
+
+fn is_current_platform(platform: &str) -> bool {
+    match platform.trim().to_lowercase().as_str() {
+        "unix" => cfg!(unix),
+        "darwin" | "macos" | "mac" => std::env::consts::OS == "macos",
+        "win" | "windows" => std::env::consts::OS == "windows",
+        other => std::env::consts::OS == other,
+    }
+}
+
+// Lexer
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Variable(String),
+    Environment(String),
+    String(String),
+    Number(String),
+    Identifier(String),
+    LeftParen,
+    RightParen,
+    Comma,
+    Not,
+    And,
+    Or,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    offset: usize,
+    length: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ExpressionError> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+
+        match c {
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                i += 1;
+                continue;
+            }
+            b'(' => {
+                tokens.push(Token {
+                    kind: TokenKind::LeftParen,
+                    offset: start,
+                    length: 1,
+                });
+                i += 1;
+            }
+            b')' => {
+                tokens.push(Token {
+                    kind: TokenKind::RightParen,
+                    offset: start,
+                    length: 1,
+                });
+                i += 1;
+            }
+            b',' => {
+                tokens.push(Token {
+                    kind: TokenKind::Comma,
+                    offset: start,
+                    length: 1,
+                });
+                i += 1;
+            }
+            b'!' => {
+                if bytes.get(i + 1) == Some(&b'=') {
+                    tokens.push(Token {
+                        kind: TokenKind::NotEqual,
+                        offset: start,
+                        length: 2,
+                    });
+                    i += 2;
+                } else {
+                    tokens.push(Token {
+                        kind: TokenKind::Not,
+                        offset: start,
+                        length: 1,
+                    });
+                    i += 1;
+                }
+            }
+            b'=' => {
+                if bytes.get(i + 1) != Some(&b'=') {
+                    return Err(ExpressionError::new(
+                        "Expected '==' for comparison",
+                        start,
+                        1,
+                    ));
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Equal,
+                    offset: start,
+                    length: 2,
+                });
+                i += 2;
+            }
+            b'&' => {
+                if bytes.get(i + 1) != Some(&b'&') {
+                    return Err(ExpressionError::new("Expected '&&'", start, 1));
+                }
+                tokens.push(Token {
+                    kind: TokenKind::And,
+                    offset: start,
+                    length: 2,
+                });
+                i += 2;
+            }
+            b'|' => {
+                if bytes.get(i + 1) != Some(&b'|') {
+                    return Err(ExpressionError::new("Expected '||'", start, 1));
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Or,
+                    offset: start,
+                    length: 2,
+                });
+                i += 2;
+            }
+            b'@' | b'$' => {
+                if bytes.get(i + 1) != Some(&b'{') {
+                    return Err(ExpressionError::new(
+                        format!("Expected '{{' after '{}'", c as char),
+                        start,
+                        1,
+                    ));
+                }
+                let Some(close) = input[i + 2..].find('}') else {
+                    return Err(ExpressionError::new(
+                        "Unclosed reference, expected '}'",
+                        start,
+                        input.len() - start,
+                    ));
+                };
+                let name = &input[i + 2..i + 2 + close];
+                let end = i + 2 + close + 1;
+
+                if c == b'@' {
+                    let valid = !name.is_empty()
+                        && name.split('.').all(|part| {
+                            !part.is_empty()
+                                && part
+                                    .chars()
+                                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+                        });
+                    if !valid {
+                        return Err(ExpressionError::new(
+                            format!("Invalid reference '@{{{}}}'", name),
+                            start,
+                            end - start,
+                        ));
+                    }
+                    tokens.push(Token {
+                        kind: TokenKind::Variable(name.to_string()),
+                        offset: start,
+                        length: end - start,
+                    });
+                } else {
+                    let valid = !name.is_empty()
+                        && !name.starts_with(|ch: char| ch.is_ascii_digit())
+                        && name
+                            .chars()
+                            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+                    if !valid {
+                        return Err(ExpressionError::new(
+                            format!("Invalid environment variable name '${{{}}}'", name),
+                            start,
+                            end - start,
+                        ));
+                    }
+                    tokens.push(Token {
+                        kind: TokenKind::Environment(name.to_string()),
+                        offset: start,
+                        length: end - start,
+                    });
+                }
+                i = end;
+            }
+            b'"' | b'\'' => {
+                let quote = c as char;
+                let mut value = String::new();
+                let mut chars = input[i + 1..].char_indices();
+                let mut end = None;
+
+                while let Some((j, ch)) = chars.next() {
+                    if ch == '\\' {
+                        if let Some((_, escaped)) = chars.next() {
+                            value.push(escaped);
+                        }
+                    } else if ch == quote {
+                        end = Some(i + 1 + j + 1);
+                        break;
+                    } else {
+                        value.push(ch);
+                    }
+                }
+
+                let Some(end) = end else {
+                    return Err(ExpressionError::new(
+                        "Unterminated string literal",
+                        start,
+                        input.len() - start,
+                    ));
+                };
+
+                tokens.push(Token {
+                    kind: TokenKind::String(value),
+                    offset: start,
+                    length: end - start,
+                });
+                i = end;
+            }
+            b'0'..=b'9' | b'-' => {
+                i += 1;
+                while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                    i += 1;
+                }
+                let text = &input[start..i];
+                if text == "-" {
+                    return Err(ExpressionError::new("Unexpected character '-'", start, 1));
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Number(text.to_string()),
+                    offset: start,
+                    length: i - start,
+                });
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Identifier(input[start..i].to_string()),
+                    offset: start,
+                    length: i - start,
+                });
+            }
+            _ => {
+                let ch = input[i..].chars().next().unwrap_or_default();
+                return Err(ExpressionError::new(
+                    format!("Unexpected character '{}'", ch),
+                    start,
+                    ch.len_utf8(),
+                ));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Parser
+
+/// Parse an expression into its syntax tree.
+pub fn parse(input: &str) -> Result<Expression, ExpressionError> {
+    let tokens = tokenize(input)?;
+
+    if tokens.is_empty() {
+        return Err(ExpressionError::new("Expression is empty", 0, input.len()));
+    }
+
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        input_len: input.len(),
+    };
+    let expression = parser.parse_or()?;
+
+    if let Some(token) = parser.peek() {
+        return Err(ExpressionError::new(
+            "Unexpected token after end of expression",
+            token.offset,
+            token.length,
+        ));
+    }
+
+    Ok(expression)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    input_len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eof_error(&self, message: &str) -> ExpressionError {
+        ExpressionError::new(message, self.input_len.saturating_sub(1), 1)
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, ExpressionError> {
+        let mut left = self.parse_and()?;
+        while matches!(self.peek().map(|t| &t.kind), Some(TokenKind::Or)) {
+            self.next();
+            let right = self.parse_and()?;
+            left = Expression::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, ExpressionError> {
+        let mut left = self.parse_unary()?;
+        while matches!(self.peek().map(|t| &t.kind), Some(TokenKind::And)) {
+            self.next();
+            let right = self.parse_unary()?;
+            left = Expression::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, ExpressionError> {
+        if matches!(self.peek().map(|t| &t.kind), Some(TokenKind::Not)) {
+            self.next();
+            let inner = self.parse_unary()?;
+            return Ok(Expression::Not(Box::new(inner)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, ExpressionError> {
+        let left = self.parse_primary()?;
+
+        match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Equal) => {
+                self.next();
+                let right = self.parse_primary()?;
+                Ok(Expression::Equal(Box::new(left), Box::new(right)))
+            }
+            Some(TokenKind::NotEqual) => {
+                self.next();
+                let right = self.parse_primary()?;
+                Ok(Expression::NotEqual(Box::new(left), Box::new(right)))
+            }
+            _ => Ok(left),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, ExpressionError> {
+        let Some(token) = self.next() else {
+            return Err(self.eof_error("Unexpected end of expression"));
+        };
+
+        match token.kind {
+            TokenKind::LeftParen => {
+                let inner = self.parse_or()?;
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::RightParen,
+                        ..
+                    }) => Ok(inner),
+                    Some(other) => Err(ExpressionError::new(
+                        "Expected ')'",
+                        other.offset,
+                        other.length,
+                    )),
+                    None => Err(ExpressionError::new(
+                        "Unclosed '(', expected ')'",
+                        token.offset,
+                        1,
+                    )),
+                }
+            }
+            TokenKind::Variable(path) => Ok(Expression::Variable(path)),
+            TokenKind::Environment(name) => Ok(Expression::Environment(name)),
+            TokenKind::String(value) => Ok(Expression::Literal(Value::String(value))),
+            TokenKind::Number(value) => Ok(Expression::Literal(Value::String(value))),
+            TokenKind::Identifier(name) => match name.as_str() {
+                "true" => Ok(Expression::Literal(Value::Bool(true))),
+                "false" => Ok(Expression::Literal(Value::Bool(false))),
+                _ => self.parse_call(&name, token.offset, token.length),
+            },
+            _ => Err(ExpressionError::new(
+                "Expected a value, reference or function call",
+                token.offset,
+                token.length,
+            )),
+        }
+    }
+
+    fn parse_call(
+        &mut self,
+        name: &str,
+        offset: usize,
+        length: usize,
+    ) -> Result<Expression, ExpressionError> {
+        let Some(function) = Function::from_name(name) else {
+            return Err(ExpressionError::new(
+                format!("Unknown function or identifier '{}'", name),
+                offset,
+                length,
+            ));
+        };
+
+        match self.next() {
+            Some(Token {
+                kind: TokenKind::LeftParen,
+                ..
+            }) => {}
+            _ => {
+                return Err(ExpressionError::new(
+                    format!("Expected '(' after '{}'", name),
+                    offset,
+                    length,
+                ));
+            }
+        }
+
+        let mut args = Vec::new();
+        let close_offset;
+
+        if matches!(self.peek().map(|t| &t.kind), Some(TokenKind::RightParen)) {
+            close_offset = self.next().map(|t| t.offset).unwrap_or(offset);
+        } else {
+            loop {
+                args.push(self.parse_or()?);
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::Comma,
+                        ..
+                    }) => continue,
+                    Some(Token {
+                        kind: TokenKind::RightParen,
+                        offset,
+                        ..
+                    }) => {
+                        close_offset = offset;
+                        break;
+                    }
+                    Some(other) => {
+                        return Err(ExpressionError::new(
+                            "Expected ',' or ')'",
+                            other.offset,
+                            other.length,
+                        ));
+                    }
+                    None => return Err(self.eof_error("Unclosed function call, expected ')'")),
+                }
+            }
+        }
+
+        if args.len() != function.arity() {
+            return Err(ExpressionError::new(
+                format!(
+                    "Function '{}' takes {} argument(s) but {} were given",
+                    name,
+                    function.arity(),
+                    args.len()
+                ),
+                offset,
+                close_offset + 1 - offset,
+            ));
+        }
+
+        Ok(Expression::Call(function, args))
+    }
+}
@@ -38,6 +38,21 @@ pub fn get_scripts_folder() -> PathBuf {
         .join("scripts")
 }
 
+/// Where the directories of past runs are kept, one folder per command.
+pub fn get_runs_folder() -> PathBuf {
+    get_home_dir().join(PROJECT_DIR).join("runs")
+}
+
+/// The age-encrypted vault inputs can read their value `from:`.
+pub fn get_secrets_vault_file() -> PathBuf {
+    get_home_dir().join(PROJECT_DIR).join("secrets.age")
+}
+
+/// The identity the secrets vault is decrypted with.
+pub fn get_secrets_identity_file() -> PathBuf {
+    get_home_dir().join(PROJECT_DIR).join("identity.txt")
+}
+
 pub fn get_command_file(path: String) -> Result<(PathBuf, Option<String>), String> {
     let yaml_path = format!("{}.yaml", path);
     let yml_path = format!("{}.yml", path);
@@ -2,9 +2,11 @@ use crate::{
     EXECUTABLE,
     cli::core::{
         CORE_COMMANDS, config_command::CONFIG_COMMAND, edit_command::EDIT_COMMAND,
-        fetch_command::FETCH_COMMAND, init_command::INIT_COMMAND, list_command::LIST_COMMAND,
-        new_command::NEW_COMMAND, validate_command::VALIDATE_COMMAND,
+        fetch_command::FETCH_COMMAND, history_command::HISTORY_COMMAND, init_command::INIT_COMMAND,
+        list_command::LIST_COMMAND, logs_command::LOGS_COMMAND, new_command::NEW_COMMAND,
+        validate_command::VALIDATE_COMMAND,
     },
+    runner::context::COMMAND_FLAGS,
     utils::{
         fs::{get_command_file, get_commands_folder},
         resolver::SECRET_MASK,
@@ -87,6 +89,8 @@ fn core_command_help_data(name: &str) -> Option<HashMap<&str, &str>> {
         "validate" => Some(VALIDATE_COMMAND.base.as_hash_map()),
         "list" => Some(LIST_COMMAND.base.as_hash_map()),
         "config" => Some(CONFIG_COMMAND.base.as_hash_map()),
+        "history" => Some(HISTORY_COMMAND.base.as_hash_map()),
+        "logs" => Some(LOGS_COMMAND.base.as_hash_map()),
         _ => None,
     }
 }
@@ -130,6 +134,14 @@ pub fn print_general_help() {
                 "name": "config",
                 "description": "Opens the configuration file in the default editor"
             },
+            {
+                "name": "history",
+                "description": "Lists past runs of commands"
+            },
+            {
+                "name": "logs",
+                "description": "Prints the output of a past or running run"
+            },
             {
                 "name": "version",
                 "description": "Display version information"
@@ -255,6 +267,25 @@ pub fn print_individual_help(command: &str) {
                         &command.replace(path::MAIN_SEPARATOR_STR, " "),
                     );
                 }
+                // Inputs of the command win over mici's own flags
+                for flag in COMMAND_FLAGS {
+                    if flag.is_shadowed_by(&cmd) {
+                        continue;
+                    }
+                    let long = format!("--{}", flag.name);
+
+                    let flag_type = match flag.hint {
+                        Some(_) => "(option)",
+                        None => "(flag)",
+                    };
+                    options.push_str(&format!(
+                        "\n    {:<16} {}\n        {}",
+                        long,
+                        flag_type.bright_black(),
+                        flag.description
+                    ));
+                }
+
                 cmd_map.insert("synopsis", synopsis.trim());
                 cmd_map.insert("options", options.trim());
 
@@ -1,7 +1,18 @@
 use regex::Regex;
-use std::{collections::BTreeMap, sync::OnceLock};
+use std::{
+    collections::BTreeMap,
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
 
-use crate::cli::schemas::v1::CommandSchemaInput;
+use crate::{
+    cli::schemas::v1::{CommandSchemaInput, CommandSchemaInputSource},
+    errors::command::CommandError,
+    utils::{
+        dotenv::parse_dotenv,
+        fs::{get_secrets_identity_file, get_secrets_vault_file},
+    },
+};
 
 pub const SECRET_MASK: &str = "***";
 
@@ -9,6 +20,9 @@ const MAX_ITERATIONS: usize = 10;
 
 static INPUTS_RE: OnceLock<Regex> = OnceLock::new();
 static ENV_RE: OnceLock<Regex> = OnceLock::new();
+static RUNTIME_RE: OnceLock<Regex> = OnceLock::new();
+static MATRIX_RE: OnceLock<Regex> = OnceLock::new();
+static ITEM_RE: OnceLock<Regex> = OnceLock::new();
 
 fn get_inputs_re() -> &'static Regex {
     INPUTS_RE.get_or_init(|| Regex::new(r"@\{inputs\.([a-zA-Z_-][a-zA-Z0-9_-]*)\}").unwrap())
@@ -18,6 +32,18 @@ fn get_env_re() -> &'static Regex {
     ENV_RE.get_or_init(|| Regex::new(r"\$\{([A-Z_][A-Z0-9_]*)\}").unwrap())
 }
 
+fn get_runtime_re() -> &'static Regex {
+    RUNTIME_RE.get_or_init(|| Regex::new(r"@\{(steps\.[a-zA-Z0-9_-]+\.[a-zA-Z0-9_.-]+)\}").unwrap())
+}
+
+fn get_matrix_re() -> &'static Regex {
+    MATRIX_RE.get_or_init(|| Regex::new(r"@\{matrix\.([a-zA-Z0-9_-]+)\}").unwrap())
+}
+
+fn get_item_re() -> &'static Regex {
+    ITEM_RE.get_or_init(|| Regex::new(r"@\{(item|item_index)\}").unwrap())
+}
+
 /// Resolve a single input variable reference to its value.
 fn resolve_input_value(
     name: &str,
@@ -53,6 +79,137 @@ fn resolve_input_value(
     }
 }
 
+/// Resolve an input to its plain string value, independent of the shell it'll be used in.
+/// Boolean inputs resolve to `true`/`false`.
+pub fn resolve_raw_input_value(
+    name: &str,
+    input: &CommandSchemaInput,
+    matches: &getopts::Matches,
+) -> String {
+    match input.r#type.as_str() {
+        "boolean" | "bool" => {
+            if matches.opt_present(name) {
+                "true".to_string()
+            } else {
+                input.default.as_deref().unwrap_or("false").to_string()
+            }
+        }
+        _ => matches
+            .opt_str(name)
+            .or_else(|| input.default.clone())
+            .unwrap_or_default(),
+    }
+}
+
+/// Read the value of inputs declaring `from:` a provider and set it as their default,
+/// so they resolve like any other input. Inputs given on the command line are left alone.
+pub fn resolve_input_sources(
+    inputs: &mut BTreeMap<String, CommandSchemaInput>,
+    matches: &getopts::Matches,
+) -> Result<(), CommandError> {
+    // Decrypted once, and only if an input reads from it
+    let mut vault: Option<BTreeMap<String, String>> = None;
+
+    for (name, input) in inputs.iter_mut() {
+        let Some(source) = &input.from else {
+            continue;
+        };
+        if matches.opt_str(name).is_some() {
+            continue;
+        }
+
+        let value = read_input_source(source, &mut vault).map_err(|message| {
+            CommandError::InputSourceFailed {
+                input_name: name.clone(),
+                source_name: source.to_string(),
+                message,
+            }
+        })?;
+        input.default = Some(value);
+    }
+
+    Ok(())
+}
+
+fn read_input_source(
+    source: &CommandSchemaInputSource,
+    vault: &mut Option<BTreeMap<String, String>>,
+) -> Result<String, String> {
+    match source {
+        CommandSchemaInputSource::File { file } => {
+            // The user's home, `MICI_HOME` only moves mici's own files
+            let path = match file.strip_prefix("~/") {
+                Some(relative) => dirs::home_dir()
+                    .ok_or("the home directory is unknown")?
+                    .join(relative),
+                None => file.into(),
+            };
+            let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+            Ok(trim_line_ending(&content))
+        }
+        CommandSchemaInputSource::Env { env } => {
+            std::env::var(env).map_err(|_| "it isn't set".to_string())
+        }
+        CommandSchemaInputSource::Command { command } => {
+            #[cfg(unix)]
+            let (shell, flag) = ("bash", "-c");
+            #[cfg(windows)]
+            let (shell, flag) = ("powershell", "-Command");
+
+            let mut cmd = Command::new(shell);
+            cmd.arg(flag).arg(command);
+            run_provider(&mut cmd).map(|output| trim_line_ending(&output))
+        }
+        CommandSchemaInputSource::Vault { vault: key } => {
+            let entries = match vault {
+                Some(entries) => entries,
+                None => vault.insert(decrypt_vault()?),
+            };
+            entries
+                .get(key)
+                .cloned()
+                .ok_or_else(|| "the vault has no such entry".to_string())
+        }
+    }
+}
+
+/// Decrypt the secrets vault with `age`. It holds one `NAME=value` line per entry.
+fn decrypt_vault() -> Result<BTreeMap<String, String>, String> {
+    let vault = get_secrets_vault_file();
+    if !vault.is_file() {
+        return Err(format!("there's no vault at {}", vault.display()));
+    }
+
+    let mut cmd = Command::new("age");
+    cmd.arg("-d")
+        .arg("-i")
+        .arg(get_secrets_identity_file())
+        .arg(&vault);
+    let content = run_provider(&mut cmd)?;
+    Ok(parse_dotenv(&content))
+}
+
+/// Run a provider and return its stdout. The terminal stays connected to its stdin and
+/// stderr, for tools asking for a passphrase.
+fn run_provider(cmd: &mut Command) -> Result<String, String> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let output = cmd
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("failed to run {}: {}", program, e))?;
+
+    if !output.status.success() {
+        return Err(format!("{} exited with {}", program, output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Values read from files and commands usually end with a newline that isn't part of them.
+fn trim_line_ending(value: &str) -> String {
+    value.trim_end_matches(['\r', '\n']).to_string()
+}
+
 pub fn resolve_environment_variables(
     environment: &BTreeMap<String, Option<String>>,
     inputs: &BTreeMap<String, CommandSchemaInput>,
@@ -184,3 +341,76 @@ pub fn resolve_input_variables(
         })
         .to_string()
 }
+
+/// Resolve references to values only known while the command runs, e.g. `@{steps.<id>.output}`.
+/// `variables` is keyed by the reference path without the `@{}` wrapper.
+pub fn resolve_runtime_variables(text: &str, variables: &BTreeMap<String, String>) -> String {
+    let runtime_re = get_runtime_re();
+
+    runtime_re
+        .replace_all(text, |caps: &regex::Captures| {
+            let path = &caps[1];
+
+            if let Some(value) = variables.get(path) {
+                value.clone()
+            } else {
+                tracing::warn!(
+                    "Unknown or unavailable reference '@{{{}}}', resolving to empty string",
+                    path
+                );
+                "".to_string()
+            }
+        })
+        .to_string()
+}
+
+/// Resolve `@{matrix.<axis>}` references to the values of one matrix combination.
+pub fn resolve_matrix_variables(text: &str, values: &BTreeMap<String, String>) -> String {
+    let matrix_re = get_matrix_re();
+
+    matrix_re
+        .replace_all(text, |caps: &regex::Captures| {
+            let axis = &caps[1];
+
+            if let Some(value) = values.get(axis) {
+                value.clone()
+            } else {
+                tracing::warn!(
+                    "Unknown matrix reference '@{{matrix.{}}}', resolving to empty string",
+                    axis
+                );
+                "".to_string()
+            }
+        })
+        .to_string()
+}
+
+/// Find all `@{matrix.<axis>}` references in `text`, as the axis name and the
+/// byte range of the whole reference.
+pub fn matrix_references(text: &str) -> Vec<(String, std::ops::Range<usize>)> {
+    get_matrix_re()
+        .captures_iter(text)
+        .filter_map(|caps| Some((caps[1].to_string(), caps.get(0)?.range())))
+        .collect()
+}
+
+/// Find all `@{steps.<id>.<field>}` references in `text`, as the step id and the field
+/// (e.g. `output` or `outputs.version`).
+pub fn step_references(text: &str) -> Vec<(String, String)> {
+    get_runtime_re()
+        .captures_iter(text)
+        .filter_map(|caps| {
+            let mut parts = caps[1].splitn(3, '.').skip(1);
+            Some((parts.next()?.to_string(), parts.next()?.to_string()))
+        })
+        .collect()
+}
+
+/// Resolve `@{item}` and `@{item_index}` to the values of one `for_each` iteration.
+pub fn resolve_item_variables(text: &str, values: &BTreeMap<String, String>) -> String {
+    get_item_re()
+        .replace_all(text, |caps: &regex::Captures| {
+            values.get(&caps[1]).cloned().unwrap_or_default()
+        })
+        .to_string()
+}
@@ -44,7 +44,9 @@ fn help_flag_long() {
         .stdout(predicate::str::contains("edit"))
         .stdout(predicate::str::contains("validate"))
         .stdout(predicate::str::contains("list"))
-        .stdout(predicate::str::contains("config"));
+        .stdout(predicate::str::contains("config"))
+        .stdout(predicate::str::contains("history"))
+        .stdout(predicate::str::contains("logs"));
 }
 
 #[test]
@@ -140,3 +142,21 @@ fn config_help() {
         .success()
         .stdout(predicate::str::contains("config"));
 }
+
+#[test]
+fn history_help() {
+    mici()
+        .args(["history", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("history"));
+}
+
+#[test]
+fn logs_help() {
+    mici()
+        .args(["logs", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("logs"));
+}
@@ -260,6 +260,230 @@ fn validate_invalid_multiple_errors() {
         .stderr(predicate::str::contains("step_id_duplicate"));
 }
 
+#[test]
+fn validate_step_when() {
+    let tmp = setup_mici_home(&[("when.yml", &fixture("valid_step_when.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "when"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn validate_invalid_step_when() {
+    let tmp = setup_mici_home(&[("bad.yml", &fixture("invalid_step_when.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("step_when_invalid"))
+        .stderr(predicate::str::contains("Expected '==' for comparison"))
+        .stderr(predicate::str::contains(
+            "Unknown function or identifier 'on_tuesday'",
+        ));
+}
+
+#[test]
+fn validate_step_depends_on() {
+    let tmp = setup_mici_home(&[("depends-on.yml", &fixture("valid_step_depends_on.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "depends-on"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn validate_invalid_step_dependencies() {
+    let tmp = setup_mici_home(&[("bad.yml", &fixture("invalid_step_dependencies.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("step_dependency_unknown"))
+        .stderr(predicate::str::contains("unknown step 'lint'"))
+        .stderr(predicate::str::contains("step_dependency_cycle"))
+        .stderr(predicate::str::contains("build -> test -> build"));
+}
+
+#[test]
+fn validate_invalid_step_retry() {
+    let tmp = setup_mici_home(&[("bad.yml", &fixture("invalid_step_retry.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("step_retry_invalid"))
+        .stderr(predicate::str::contains("'attempts' must be at least 1"))
+        .stderr(predicate::str::contains("'backoff' must be at least 1"))
+        .stderr(predicate::str::contains("duration_invalid"));
+}
+
+#[test]
+fn validate_invalid_timeout() {
+    let tmp = setup_mici_home(&[("bad.yml", &fixture("invalid_timeout.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("3 validation error(s)"))
+        .stderr(predicate::str::contains("unknown unit 'minutes'"))
+        .stderr(predicate::str::contains("unknown unit 'x'"))
+        .stderr(predicate::str::contains("duration is too long"));
+}
+
+#[test]
+fn validate_cleanup() {
+    let tmp = setup_mici_home(&[("cleanup.yml", &fixture("valid_cleanup.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "cleanup"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn validate_invalid_cleanup_duplicate_id() {
+    let tmp = setup_mici_home(&[("bad.yml", &fixture("invalid_cleanup_duplicate_id.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("step_id_duplicate"));
+}
+
+#[test]
+fn validate_invalid_rollback() {
+    let tmp = setup_mici_home(&[("bad.yml", &fixture("invalid_rollback.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("step_run_mutually_exclusive"))
+        .stderr(predicate::str::contains("in its 'rollback'"));
+}
+
+#[test]
+fn validate_invalid_matrix() {
+    let tmp = setup_mici_home(&[("bad.yml", &fixture("invalid_matrix.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("step_matrix_invalid"))
+        .stderr(predicate::str::contains("axis 'profile' has no values"))
+        .stderr(predicate::str::contains("step_matrix_reference_undefined"))
+        .stderr(predicate::str::contains("undefined matrix axis 'arch'"));
+}
+
+#[test]
+fn validate_invalid_matrix_ids() {
+    let tmp = setup_mici_home(&[("matrix-ids.yml", &fixture("invalid_matrix_ids.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "matrix-ids"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("step_matrix_id_duplicate"))
+        .stderr(predicate::str::contains("build-linux_arm"))
+        .stderr(predicate::str::contains("test-linux"));
+}
+
+#[test]
+fn validate_invalid_interactive_parallel() {
+    let tmp = setup_mici_home(&[("bad.yml", &fixture("invalid_interactive_parallel.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("step_interactive_parallel"))
+        .stderr(predicate::str::contains(
+            "Step 'login' is interactive and can't run in a parallel group",
+        ));
+}
+
+#[test]
+fn validate_invalid_step_stdin() {
+    let tmp = setup_mici_home(&[("bad.yml", &fixture("invalid_step_stdin.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("step_stdin_invalid"))
+        .stderr(predicate::str::contains("unknown source 'terminal'"))
+        .stderr(predicate::str::contains(
+            "step 'later' isn't declared before this step",
+        ))
+        .stderr(predicate::str::contains(
+            "step 'fetch' may still be running when this step starts",
+        ))
+        .stderr(predicate::str::contains(
+            "Step 'report' has an invalid 'stdin'",
+        ));
+}
+
+#[test]
+fn validate_invalid_pass_environment() {
+    let tmp = setup_mici_home(&[("bad.yml", &fixture("invalid_pass_environment.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pass_environment_unused"));
+}
+
+#[test]
+fn validate_invalid_success_codes() {
+    let tmp = setup_mici_home(&[("bad.yml", &fixture("invalid_success_codes.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("step_success_codes_empty"))
+        .stderr(predicate::str::contains(
+            "Step 'check' has no 'success_codes'",
+        ));
+}
+
+#[test]
+fn validate_invalid_input_from() {
+    let tmp = setup_mici_home(&[("bad.yml", &fixture("invalid_input_from.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["validate", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("input_from_requires_secret"));
+}
+
 // ─── Config validation ───
 
 #[test]
@@ -275,254 +499,1319 @@ fn config_warns_on_unknown_keys() {
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .args(["list"])
+        .args(["list"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Unknown config key 'typo_key'"));
+}
+
+#[test]
+fn config_no_warning_on_valid_keys() {
+    let tmp = setup_mici_home(&[("hello.yml", &fixture("valid_command.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["list"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Unknown config key").not());
+}
+
+// ─── List ───
+
+#[test]
+fn list_shows_commands() {
+    let tmp = setup_mici_home(&[("hello.yml", &fixture("valid_command.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("hello"));
+}
+
+#[test]
+fn list_shows_multiple_commands() {
+    let tmp = setup_mici_home(&[
+        ("hello.yml", &fixture("valid_command.yml")),
+        ("minimal.yml", &fixture("minimal_command.yml")),
+    ]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("hello"))
+        .stdout(predicate::str::contains("minimal"));
+}
+
+// ─── Run: success ───
+
+#[test]
+fn run_simple_command() {
+    let tmp = setup_mici_home(&[("minimal.yml", &fixture("minimal_command.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("minimal")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ok"));
+}
+
+#[test]
+fn run_command_with_input() {
+    let tmp = setup_mici_home(&[("hello.yml", &fixture("valid_command.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["hello", "--name", "Rust"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Hello, Rust!"));
+}
+
+#[test]
+fn run_command_with_default_input() {
+    let tmp = setup_mici_home(&[("hello.yml", &fixture("valid_command.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Hello, World!"));
+}
+
+#[test]
+fn run_multi_step() {
+    let tmp = setup_mici_home(&[("multi.yml", &fixture("valid_multi_step.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("multi")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("step-1"))
+        .stdout(predicate::str::contains("step-2"))
+        .stdout(predicate::str::contains("step-3"));
+}
+
+// Env var and input resolution tests use shell-specific syntax:
+//   Unix (bash):       echo $VAR
+//   Windows (PowerShell): echo $env:VAR
+
+#[cfg(unix)]
+#[test]
+fn run_env_vars() {
+    let tmp = setup_mici_home(&[("env-vars.yml", &fixture("valid_env_vars.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("env-vars")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("global-value"))
+        .stdout(predicate::str::contains("step-override"));
+}
+
+#[cfg(windows)]
+#[test]
+fn run_env_vars() {
+    let tmp = setup_mici_home(&[("env-vars.yml", &fixture("valid_env_vars_windows.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("env-vars")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("global-value"))
+        .stdout(predicate::str::contains("step-override"));
+}
+
+#[cfg(unix)]
+#[test]
+fn run_input_resolution_with_args() {
+    let tmp = setup_mici_home(&[("input-res.yml", &fixture("valid_input_resolution.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["input-res", "--greeting", "Hi", "--target", "Earth"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Hi, Earth!"));
+}
+
+#[cfg(windows)]
+#[test]
+fn run_input_resolution_with_args() {
+    let tmp = setup_mici_home(&[(
+        "input-res.yml",
+        &fixture("valid_input_resolution_windows.yml"),
+    )]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["input-res", "--greeting", "Hi", "--target", "Earth"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Hi, Earth!"));
+}
+
+#[cfg(unix)]
+#[test]
+fn run_input_resolution_defaults() {
+    let tmp = setup_mici_home(&[("input-res.yml", &fixture("valid_input_resolution.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("input-res")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Hello, World!"));
+}
+
+#[cfg(windows)]
+#[test]
+fn run_input_resolution_defaults() {
+    let tmp = setup_mici_home(&[(
+        "input-res.yml",
+        &fixture("valid_input_resolution_windows.yml"),
+    )]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("input-res")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Hello, World!"));
+}
+
+#[test]
+fn run_choice_input_with_value() {
+    let tmp = setup_mici_home(&[("choice.yml", &fixture("valid_choice_input.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["choice", "--env", "staging"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("deploying to staging"));
+}
+
+#[test]
+fn run_choice_input_default() {
+    let tmp = setup_mici_home(&[("choice.yml", &fixture("valid_choice_input.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("choice")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("deploying to production"));
+}
+
+#[test]
+fn run_bool_input_present() {
+    let tmp = setup_mici_home(&[("booltest.yml", &fixture("valid_bool_input.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["booltest", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("(?i)dry_run=true").unwrap());
+}
+
+#[test]
+fn run_bool_input_absent() {
+    let tmp = setup_mici_home(&[("booltest.yml", &fixture("valid_bool_input.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("booltest")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("(?i)dry_run=false").unwrap());
+}
+
+#[test]
+fn run_no_inputs_command() {
+    let tmp = setup_mici_home(&[("no-inputs.yml", &fixture("valid_no_inputs.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("no-inputs")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("no inputs needed"));
+}
+
+#[cfg(unix)]
+#[test]
+fn run_step_when_defaults() {
+    let tmp = setup_mici_home(&[("when.yml", &fixture("valid_step_when.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("when")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("always-runs"))
+        .stdout(predicate::str::contains("staging-only"))
+        .stdout(predicate::str::contains("production-only").not())
+        .stdout(predicate::str::contains("notified").not());
+}
+
+#[cfg(unix)]
+#[test]
+fn run_step_when_with_inputs() {
+    let tmp = setup_mici_home(&[("when.yml", &fixture("valid_step_when.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["when", "--env", "production", "--notify"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("staging-only").not())
+        .stdout(predicate::str::contains("production-only"))
+        .stdout(predicate::str::contains("notified"));
+}
+
+#[test]
+fn run_step_when_on_failure() {
+    let tmp = setup_mici_home(&[("when-failure.yml", &fixture("valid_step_when_failure.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("when-failure")
+        .assert()
+        .failure()
+        .code(7)
+        .stdout(predicate::str::contains("handled failure"))
+        .stdout(predicate::str::contains("should-not-run").not());
+}
+
+#[test]
+#[cfg(unix)]
+fn run_step_outputs() {
+    let tmp = setup_mici_home(&[("step-outputs.yml", &fixture("valid_step_outputs.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("step-outputs")
+        .assert()
+        .failure()
+        .code(3)
+        .stdout(predicate::str::contains("version is 1.2.3"))
+        .stdout(predicate::str::contains("env sees 1.2.3"))
+        .stdout(predicate::str::contains("probe exited with 3 (failure)"));
+}
+
+#[test]
+#[cfg(unix)]
+fn run_step_output_file() {
+    let tmp = setup_mici_home(&[("output-file.yml", &fixture("valid_step_output_file.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("output-file")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("version=2.0.1"))
+        .stdout(predicate::str::contains("notes=line one|line two"));
+}
+
+#[test]
+#[cfg(unix)]
+fn run_step_env_file() {
+    let tmp = setup_mici_home(&[("env-file.yml", &fixture("valid_step_env_file.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("env-file")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("exported=from-step"))
+        .stdout(predicate::str::contains("overridden=from-step-environment"))
+        .stdout(predicate::str::contains("layered=from-step"))
+        .stdout(predicate::str::contains("tool says hi"));
+}
+
+#[test]
+#[cfg(unix)]
+fn run_step_files_are_private() {
+    let tmp = setup_mici_home(&[(
+        "private-step-files.yml",
+        &fixture("valid_private_step_files.yml"),
+    )]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("private-step-files")
+        .assert()
+        .success()
+        .stdout("-rw-------\n-rw-------\ndrwx------\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn run_step_retry() {
+    let tmp = setup_mici_home(&[("retry.yml", &fixture("valid_step_retry.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("retry")
+        .assert()
+        .failure()
+        .code(4)
+        .stdout(predicate::str::contains("flaky succeeded"))
+        .stderr(predicate::str::contains("attempt 2/3"))
+        .stderr(predicate::str::contains(
+            "Step 'broken' failed with exit code: 4 (after 2 attempts)",
+        ));
+
+    let attempts = |name: &str| {
+        std::fs::read_to_string(tmp.path().join(name))
+            .unwrap()
+            .lines()
+            .count()
+    };
+    assert_eq!(attempts("flaky-attempts"), 3);
+    assert_eq!(attempts("not-retried-attempts"), 1);
+}
+
+#[test]
+#[cfg(unix)]
+fn run_step_timeout() {
+    let tmp = setup_mici_home(&[("timeout.yml", &fixture("valid_step_timeout.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("timeout")
+        .timeout(std::time::Duration::from_secs(10))
+        .assert()
+        .failure()
+        .code(124)
+        .stdout(predicate::str::contains("should-not-run").not())
+        .stderr(predicate::str::contains("exceeded the step timeout"))
+        .stderr(predicate::str::contains("Step 'hang' timed out after"));
+
+    // The background process was in the step's process group and got terminated too.
+    // It may linger as a zombie until its new parent reaps it.
+    let pid = std::fs::read_to_string(tmp.path().join("background.pid")).unwrap();
+    let pid = pid.trim();
+    let running = || {
+        let exists = std::process::Command::new("kill")
+            .args(["-0", pid])
+            .status()
+            .unwrap()
+            .success();
+        let zombie = std::fs::read_to_string(format!("/proc/{}/stat", pid)).is_ok_and(|stat| {
+            stat.rsplit(')')
+                .next()
+                .unwrap_or("")
+                .trim_start()
+                .starts_with('Z')
+        });
+        exists && !zombie
+    };
+    assert!(!running(), "background process {} is still running", pid);
+}
+
+#[test]
+#[cfg(unix)]
+fn run_command_timeout() {
+    let tmp = setup_mici_home(&[("command-timeout.yml", &fixture("valid_command_timeout.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("command-timeout")
+        .timeout(std::time::Duration::from_secs(10))
+        .assert()
+        .failure()
+        .code(124)
+        .stderr(predicate::str::contains("exceeded the command timeout"))
+        .stderr(predicate::str::contains("Step 'second' timed out after"));
+}
+
+#[test]
+#[cfg(unix)]
+fn run_step_continue_on_error() {
+    let tmp = setup_mici_home(&[(
+        "continue-on-error.yml",
+        &fixture("valid_continue_on_error.yml"),
+    )]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("continue-on-error")
+        .assert()
+        .failure()
+        .code(10)
+        .stdout(predicate::str::contains("cache=failure lint=success (3)"))
+        .stdout(predicate::str::contains("dependents still run"))
+        .stderr(predicate::str::contains("Summary: 3 succeeded, 1 failed"))
+        .stderr(predicate::str::contains(
+            "Steps failed with continue_on_error set: cache",
+        ));
+}
+
+#[test]
+fn run_cleanup_after_failure() {
+    let tmp = setup_mici_home(&[("cleanup.yml", &fixture("valid_cleanup.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("cleanup")
+        .assert()
+        .failure()
+        .code(4)
+        .stdout(predicate::str::contains("should not publish").not())
+        .stdout(predicate::str::contains("notify runs anyway"))
+        .stdout(predicate::str::contains(
+            "cleanup after failure (failure, build=failure)",
+        ))
+        .stdout(predicate::str::contains("reporting failure"))
+        .stdout(predicate::str::contains("should not tag").not())
+        .stderr(predicate::str::contains(
+            "Cleanup summary: 2 succeeded, 1 skipped",
+        ));
+}
+
+/// Run a command, send `signal` to mici once the step printed `started`, and collect
+/// the exit status with the remaining stdout and all of stderr.
+#[cfg(unix)]
+fn signal_after_output(
+    tmp: &tempfile::TempDir,
+    command: &str,
+    started: &str,
+    signal: &str,
+) -> (std::process::ExitStatus, String, String) {
+    use std::io::{BufRead, BufReader, Read};
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_mici"))
+        .env("MICI_HOME", tmp.path())
+        .arg(command)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    while !line.contains(started) {
+        line.clear();
+        assert_ne!(
+            stdout.read_line(&mut line).unwrap(),
+            0,
+            "step never started"
+        );
+    }
+
+    std::process::Command::new("kill")
+        .args([&format!("-{}", signal), &child.id().to_string()])
+        .status()
+        .unwrap();
+
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+
+    (child.wait().unwrap(), rest, stderr)
+}
+
+#[test]
+#[cfg(unix)]
+fn run_cleanup_after_interrupt() {
+    let tmp = setup_mici_home(&[(
+        "cleanup-interrupt.yml",
+        &fixture("valid_cleanup_interrupt.yml"),
+    )]);
+
+    let (status, rest, stderr) = signal_after_output(&tmp, "cleanup-interrupt", "waiting", "INT");
+
+    assert_eq!(status.code(), Some(130), "stderr: {}", stderr);
+    assert!(rest.contains("cleanup after cancelled"), "stdout: {}", rest);
+    assert!(!rest.contains("should not run"), "stdout: {}", rest);
+}
+
+#[test]
+#[cfg(unix)]
+fn run_cleanup_stops_on_another_signal() {
+    use std::io::{BufRead, BufReader, Read};
+
+    let tmp = setup_mici_home(&[("cleanup-signal.yml", &fixture("valid_cleanup_signal.yml"))]);
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_mici"))
+        .env("MICI_HOME", tmp.path())
+        .arg("cleanup-signal")
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let started = std::time::Instant::now();
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    for (expected, signal) in [("waiting", "INT"), ("cleaning", "TERM")] {
+        let mut line = String::new();
+        while !line.contains(expected) {
+            line.clear();
+            assert_ne!(
+                stdout.read_line(&mut line).unwrap(),
+                0,
+                "{} is missing",
+                expected
+            );
+        }
+        std::process::Command::new("kill")
+            .args([&format!("-{}", signal), &child.id().to_string()])
+            .status()
+            .unwrap();
+    }
+
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+
+    assert_eq!(
+        child.wait().unwrap().code(),
+        Some(130),
+        "stderr: {}",
+        stderr
+    );
+    assert!(
+        started.elapsed() < std::time::Duration::from_secs(20),
+        "cleanup was waited out"
+    );
+    assert!(!rest.contains("should not"), "stdout: {}", rest);
+    assert!(stderr.contains("Received SIGTERM"), "stderr: {}", stderr);
+}
+
+#[test]
+#[cfg(unix)]
+fn run_forwards_signal_to_step() {
+    let tmp = setup_mici_home(&[("forward.yml", &fixture("valid_signal_forwarding.yml"))]);
+
+    let (status, rest, stderr) = signal_after_output(&tmp, "forward", "waiting", "HUP");
+
+    assert_eq!(status.code(), Some(129), "stderr: {}", stderr);
+    assert!(rest.contains("step got HUP"), "stdout: {}", rest);
+    assert!(rest.contains("cleaned up"), "stdout: {}", rest);
+    assert!(
+        stderr.contains("Interrupted by SIGHUP"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn run_step_killed_by_signal() {
+    let tmp = setup_mici_home(&[("killed.yml", &fixture("valid_step_killed.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("killed")
+        .assert()
+        .code(137)
+        .stderr(predicate::str::contains(
+            "Step 'crash' was killed by SIGKILL",
+        ));
+}
+
+#[test]
+fn run_rollback_after_failure() {
+    let tmp = setup_mici_home(&[("rollback.yml", &fixture("valid_rollback.yml"))]);
+
+    let output = mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("rollback")
+        .assert()
+        .failure()
+        .code(5)
+        .stdout(predicate::str::contains("should not undo").not())
+        .stderr(predicate::str::contains("Rolling back 2 steps"))
+        .stderr(predicate::str::contains("Rolled back: migrate"))
+        .stderr(predicate::str::contains("Rollback failed: deploy"))
+        .get_output()
+        .stdout
+        .clone();
+
+    let stdout = String::from_utf8(output).unwrap();
+    let deploy = stdout
+        .find("undo deploy of v2")
+        .expect("deploy was not rolled back");
+    let migrate = stdout
+        .find("undo migrate")
+        .expect("migrate was not rolled back");
+    assert!(deploy < migrate, "rollbacks ran in the wrong order");
+}
+
+#[test]
+fn run_rollback_spawn_error_still_cleans_up() {
+    let tmp = setup_mici_home(&[(
+        "rollback-spawn-error.yml",
+        &fixture("valid_rollback_spawn_error.yml"),
+    )]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("rollback-spawn-error")
+        .assert()
+        .failure()
+        .code(4)
+        .stdout(predicate::str::contains("cleaned up"))
+        .stdout(predicate::str::contains("should not run").not())
+        .stderr(predicate::str::contains(
+            "Rollback of step 'prepare' could not run",
+        ))
+        .stderr(predicate::str::contains("Rollback failed: prepare"));
+}
+
+#[test]
+fn run_step_matrix() {
+    let tmp = setup_mici_home(&[("matrix.yml", &fixture("valid_matrix.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("matrix")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("build x86_64 dev (dev)"))
+        .stdout(predicate::str::contains("build x86_64 release (release)"))
+        .stdout(predicate::str::contains("build aarch64 dev (dev)"))
+        .stdout(predicate::str::contains("build aarch64 release (release)"))
+        .stdout(predicate::str::contains("release only").count(1))
+        .stdout(predicate::str::contains("package after success"))
+        .stderr(predicate::str::contains("publish-dev (skipped)"))
+        .stderr(predicate::str::contains("Summary: 6 succeeded, 1 skipped"));
+}
+
+#[test]
+fn run_step_for_each() {
+    let tmp = setup_mici_home(&[("for-each.yml", &fixture("valid_for_each.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["for-each", "--hosts", "db1 db2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("deploy api (0/0)"))
+        .stdout(predicate::str::contains("deploy worker (2/2)"))
+        .stdout(predicate::str::contains("deploy web").not())
+        .stdout(predicate::str::contains("notify after success"))
+        .stdout(predicate::str::contains("host db1"))
+        .stdout(predicate::str::contains("host db2"))
+        .stderr(predicate::str::contains(
+            "Step 'deploy' items: api (success), web (skipped), worker (failure)",
+        ))
+        .stderr(predicate::str::contains(
+            "Step 'check' items: db1 (success), db2 (success)",
+        ));
+}
+
+#[test]
+#[cfg(unix)]
+fn run_step_for_each_without_items() {
+    let tmp = setup_mici_home(&[("for-each-empty.yml", &fixture("valid_for_each_empty.yml"))]);
+
+    let output = mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("for-each-empty")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("deploy").not())
+        .stdout(predicate::str::contains("verify").not())
+        .stderr(predicate::str::contains(
+            "Step 'deploy' has no items to run for, skipping it",
+        ))
+        .get_output()
+        .stdout
+        .clone();
+
+    let stdout = String::from_utf8(output).unwrap();
+    let prepared = stdout.find("prepared").expect("prepare did not run");
+    let notified = stdout
+        .find("notify after skipped")
+        .expect("notify did not run");
+    assert!(prepared < notified, "notify ran before the steps before it");
+}
+
+#[test]
+fn run_step_for_each_duplicate_id() {
+    let tmp = setup_mici_home(&[("for-each-ids.yml", &fixture("invalid_for_each_ids.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("for-each-ids")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("should not run").not())
+        .stdout(predicate::str::contains("deploy api").not())
+        .stderr(predicate::str::contains(
+            "More than one step runs as 'deploy-api'",
+        ));
+}
+
+#[test]
+#[cfg(unix)]
+fn run_step_for_each_item_with_quotes() {
+    let tmp = setup_mici_home(&[("for-each-quotes.yml", &fixture("valid_for_each_quotes.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("for-each-quotes")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#"item: it's "quoted" \ too"#))
+        .stdout(predicate::str::contains("item: skip").not());
+}
+
+#[test]
+fn run_dry_run() {
+    let tmp = setup_mici_home(&[("dry-run.yml", &fixture("valid_dry_run.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .current_dir(tmp.path())
+        .args(["dry-run", "--token", "hunter2", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Step 1/2: deploy"))
+        .stdout(predicate::str::contains(
+            "echo \"deploying release with ***\"; touch deployed",
+        ))
+        .stdout(predicate::str::contains("echo undo"))
+        .stdout(predicate::str::contains("AUTH=Bearer ***"))
+        .stdout(predicate::str::contains("DEPLOY_CHANNEL=release"))
+        .stdout(predicate::str::contains("MICI_INPUT_TOKEN=***"))
+        .stdout(predicate::str::contains(
+            "@{steps.deploy.outcome} == 'success'",
+        ))
+        .stdout(predicate::str::contains("Cleanup step 1/1: cleanup"))
+        .stdout(predicate::str::contains("hunter2").not());
+
+    assert!(!tmp.path().join("deployed").exists());
+}
+
+#[test]
+fn run_dry_run_input_shadows_flag() {
+    let tmp = setup_mici_home(&[("booltest.yml", &fixture("valid_bool_input.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["booltest", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("dry_run=true"))
+        .stderr(predicate::str::contains(
+            "so it's passed to the command instead of being handled by mici",
+        ));
+}
+
+#[test]
+fn run_undashed_input_shadows_flag() {
+    let tmp = setup_mici_home(&[("shadow.yml", &fixture("valid_input_shadows_flag.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["shadow", "--only", "web"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("only=web"))
+        .stderr(predicate::str::contains(
+            "Input 'only' uses --only, so it's passed to the command",
+        ));
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["shadow", "--help"])
         .assert()
         .success()
-        .stderr(predicate::str::contains("Unknown config key 'typo_key'"));
+        .stdout(predicate::str::contains("Services to deploy"))
+        .stdout(predicate::str::contains("Only run the steps").not())
+        .stdout(predicate::str::contains("--skip"));
 }
 
 #[test]
-fn config_no_warning_on_valid_keys() {
-    let tmp = setup_mici_home(&[("hello.yml", &fixture("valid_command.yml"))]);
+#[cfg(unix)]
+fn run_step_selection_from_step() {
+    let tmp = setup_mici_home(&[("select.yml", &fixture("valid_step_selection.yml"))]);
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .args(["list"])
+        .args(["select", "--from-step", "test"])
         .assert()
         .success()
-        .stderr(predicate::str::contains("Unknown config key").not());
+        .stdout(predicate::str::contains("building").not())
+        .stdout(predicate::str::contains("testing"))
+        .stdout(predicate::str::contains("deploying"))
+        .stdout(predicate::str::contains("cleaning up"))
+        .stderr(predicate::str::contains("build (skipped, not selected)"))
+        .stderr(predicate::str::contains(
+            "Step 'deploy' uses results of step 'build', which isn't selected",
+        ));
 }
 
-// ─── List ───
-
 #[test]
-fn list_shows_commands() {
-    let tmp = setup_mici_home(&[("hello.yml", &fixture("valid_command.yml"))]);
+#[cfg(unix)]
+fn run_step_selection_only_and_skip() {
+    let tmp = setup_mici_home(&[("select.yml", &fixture("valid_step_selection.yml"))]);
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .args(["list"])
+        .args(["select", "--only", "build,deploy", "--skip", "deploy"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("hello"));
+        .stdout(predicate::str::contains("building"))
+        .stdout(predicate::str::contains("testing").not())
+        .stdout(predicate::str::contains("deploying").not())
+        .stdout(predicate::str::contains("cleaning up"))
+        .stderr(predicate::str::contains("isn't selected").not());
 }
 
 #[test]
-fn list_shows_multiple_commands() {
-    let tmp = setup_mici_home(&[
-        ("hello.yml", &fixture("valid_command.yml")),
-        ("minimal.yml", &fixture("minimal_command.yml")),
-    ]);
+fn run_step_selection_from_step_takes_one_id() {
+    let tmp = setup_mici_home(&[("select.yml", &fixture("valid_step_selection.yml"))]);
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .args(["list"])
+        .args(["select", "--from-step", "deploy,build"])
         .assert()
-        .success()
-        .stdout(predicate::str::contains("hello"))
-        .stdout(predicate::str::contains("minimal"));
-}
+        .failure()
+        .stdout(predicate::str::contains("building").not())
+        .stderr(predicate::str::contains(
+            "--from-step takes a single step id, got 'deploy,build'",
+        ));
 
-// ─── Run: success ───
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["select", "--from-step", "deploy", "--from-step", "build"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("building").not())
+        .stderr(predicate::str::contains("given more than once"));
+}
 
 #[test]
-fn run_simple_command() {
-    let tmp = setup_mici_home(&[("minimal.yml", &fixture("minimal_command.yml"))]);
+fn run_step_selection_unknown_step() {
+    let tmp = setup_mici_home(&[("select.yml", &fixture("valid_step_selection.yml"))]);
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .arg("minimal")
+        .args(["select", "--skip", "tset"])
         .assert()
-        .success()
-        .stdout(predicate::str::contains("ok"));
+        .failure()
+        .stdout(predicate::str::contains("testing").not())
+        .stderr(predicate::str::contains(
+            "Unknown step 'tset' given to --skip",
+        ))
+        .stderr(predicate::str::contains("build, test, deploy"));
 }
 
 #[test]
-fn run_command_with_input() {
-    let tmp = setup_mici_home(&[("hello.yml", &fixture("valid_command.yml"))]);
+#[cfg(unix)]
+fn run_interactive_step() {
+    let tmp = setup_mici_home(&[("interactive.yml", &fixture("valid_interactive.yml"))]);
 
-    mici()
+    let output = mici()
         .env("MICI_HOME", tmp.path())
-        .args(["hello", "--name", "Rust"])
+        .arg("interactive")
+        .write_stdin("alice\n")
         .assert()
         .success()
-        .stdout(predicate::str::contains("Hello, Rust!"));
+        .get_output()
+        .stdout
+        .clone();
+    let stdout = String::from_utf8_lossy(&output);
+
+    // The interactive step waits for the step already running
+    let background = stdout.find("background done").expect("background step ran");
+    let ask = stdout
+        .find("hello alice")
+        .expect("interactive step read stdin");
+    assert!(background < ask, "stdout: {}", stdout);
 }
 
 #[test]
-fn run_command_with_default_input() {
-    let tmp = setup_mici_home(&[("hello.yml", &fixture("valid_command.yml"))]);
+#[cfg(unix)]
+fn run_step_stdin() {
+    let tmp = setup_mici_home(&[("stdin.yml", &fixture("valid_step_stdin.yml"))]);
+    let data_dir = tmp.path().join("data");
+    std::fs::create_dir(&data_dir).unwrap();
+    std::fs::write(data_dir.join("data.txt"), "from a file\n").unwrap();
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .arg("hello")
+        .args(["stdin", "--data_dir", data_dir.to_str().unwrap()])
+        .write_stdin("from mici\n")
         .assert()
         .success()
-        .stdout(predicate::str::contains("Hello, World!"));
+        .stdout(predicate::str::contains("text: hello world"))
+        .stdout(predicate::str::contains("file: from a file"))
+        .stdout(predicate::str::contains("lines: 2"))
+        .stdout(predicate::str::contains("forwarded: from mici"));
 }
 
 #[test]
-fn run_multi_step() {
-    let tmp = setup_mici_home(&[("multi.yml", &fixture("valid_multi_step.yml"))]);
+#[cfg(unix)]
+fn run_stdin_confirm_needs_terminal() {
+    let tmp = setup_mici_home(&[("stdin-confirm.yml", &fixture("valid_stdin_confirm.yml"))]);
 
+    // The piped "y" is data for the step, not the confirmation
     mici()
         .env("MICI_HOME", tmp.path())
-        .arg("multi")
+        .current_dir(tmp.path())
+        .arg("stdin-confirm")
+        .write_stdin("y\n")
         .assert()
-        .success()
-        .stdout(predicate::str::contains("step-1"))
-        .stdout(predicate::str::contains("step-2"))
-        .stdout(predicate::str::contains("step-3"));
-}
+        .failure()
+        .stderr(predicate::str::contains(
+            "No terminal to ask for confirmation on, stdin is forwarded to a step",
+        ));
 
-// Env var and input resolution tests use shell-specific syntax:
-//   Unix (bash):       echo $VAR
-//   Windows (PowerShell): echo $env:VAR
+    assert!(!tmp.path().join("restored").exists());
+}
 
-#[cfg(unix)]
 #[test]
-fn run_env_vars() {
-    let tmp = setup_mici_home(&[("env-vars.yml", &fixture("valid_env_vars.yml"))]);
+fn run_dotenv() {
+    let tmp = setup_mici_home(&[("dotenv.yml", &fixture("valid_dotenv.yml"))]);
+    std::fs::write(
+        tmp.path().join("base.env"),
+        "# shared\nBASE=base\nSTAGE=dev\nFROM_CONFIG_FILE=config-file\nexport QUOTED=\"a \\\"b\\\" # kept\"\n",
+    )
+    .unwrap();
+    std::fs::write(tmp.path().join("prod.env"), "STAGE=prod # the stage\n").unwrap();
+    std::fs::write(
+        tmp.path().join("step.env"),
+        "STEP_FILE='step-file'\nFROM_STEP_FILE=step-file\n",
+    )
+    .unwrap();
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .arg("env-vars")
+        .current_dir(tmp.path())
+        .args(["dotenv", "--stage", "prod"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("global-value"))
-        .stdout(predicate::str::contains("step-override"));
+        .stdout(predicate::str::contains(
+            "layers: base prod config step-file step",
+        ))
+        .stdout(predicate::str::contains(
+            "config file: overridden by the config environment",
+        ))
+        .stdout(predicate::str::contains(
+            "step file: overridden by the step environment",
+        ))
+        .stdout(predicate::str::contains("quoted: a \"b\" # kept"));
 }
 
-#[cfg(windows)]
 #[test]
-fn run_env_vars() {
-    let tmp = setup_mici_home(&[("env-vars.yml", &fixture("valid_env_vars_windows.yml"))]);
+fn run_dotenv_missing_file() {
+    let tmp = setup_mici_home(&[("dotenv-missing.yml", &fixture("invalid_dotenv_missing.yml"))]);
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .arg("env-vars")
+        .current_dir(tmp.path())
+        .arg("dotenv-missing")
         .assert()
-        .success()
-        .stdout(predicate::str::contains("global-value"))
-        .stdout(predicate::str::contains("step-override"));
+        .failure()
+        .stdout(predicate::str::contains("should not run").not())
+        .stderr(predicate::str::contains("env_file_not_found"))
+        .stderr(predicate::str::contains("env_file: \"missing.env\""));
 }
 
-#[cfg(unix)]
 #[test]
-fn run_input_resolution_with_args() {
-    let tmp = setup_mici_home(&[("input-res.yml", &fixture("valid_input_resolution.yml"))]);
+fn run_clean_environment() {
+    let tmp = setup_mici_home(&[("clean-env.yml", &fixture("valid_clean_environment.yml"))]);
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .args(["input-res", "--greeting", "Hi", "--target", "Earth"])
+        .env("MICI_TEST_PASS_ONE", "one")
+        .env("MICI_TEST_LEAK", "leak")
+        .args(["clean-env", "--name", "world"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Hi, Earth!"));
+        .stdout(predicate::str::contains("passed: one"))
+        .stdout(predicate::str::contains("leaked: unset"))
+        .stdout(predicate::str::contains("explicit: leak"))
+        .stdout(predicate::str::contains("input: world"));
 }
 
-#[cfg(windows)]
 #[test]
-fn run_input_resolution_with_args() {
-    let tmp = setup_mici_home(&[(
-        "input-res.yml",
-        &fixture("valid_input_resolution_windows.yml"),
-    )]);
+fn run_masks_secrets() {
+    let tmp = setup_mici_home(&[("secrets.yml", &fixture("valid_secret_masking.yml"))]);
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .args(["input-res", "--greeting", "Hi", "--target", "Earth"])
+        .args(["secrets", "--token", "first-half\nsecond-half"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Hi, Earth!"));
+        .stdout(predicate::str::contains("token: ***"))
+        .stdout(predicate::str::contains("captured: token: ***"))
+        .stdout(predicate::str::contains("first-half"))
+        .stdout(predicate::str::contains("second-half").not())
+        .stderr(predicate::str::contains("key: ***"))
+        .stderr(predicate::str::contains("Ignoring malformed line 2"))
+        .stderr(predicate::str::contains("abc123xyz").not())
+        .stderr(predicate::str::contains("first-half").not());
 }
 
 #[cfg(unix)]
 #[test]
-fn run_input_resolution_defaults() {
-    let tmp = setup_mici_home(&[("input-res.yml", &fixture("valid_input_resolution.yml"))]);
+fn run_secret_sources() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = setup_mici_home(&[("secret-sources.yml", &fixture("valid_secret_sources.yml"))]);
+    std::fs::write(tmp.path().join("token.txt"), "from-file\n").unwrap();
+    // Apart from MICI_HOME, where a file of the same name must not be read
+    let home = tmp.path().join("home");
+    std::fs::create_dir_all(home.join(".config/deploy")).unwrap();
+    std::fs::write(home.join(".config/deploy/token"), "from-home\n").unwrap();
+    std::fs::create_dir_all(tmp.path().join(".config/deploy")).unwrap();
+    std::fs::write(tmp.path().join(".config/deploy/token"), "from-mici-home\n").unwrap();
+    std::fs::write(tmp.path().join(".mici/identity.txt"), "").unwrap();
+    std::fs::write(
+        tmp.path().join(".mici/secrets.age"),
+        "deploy_token=from-vault\n",
+    )
+    .unwrap();
+
+    // Stands in for age, the vault is stored in plain text
+    let bin = tmp.path().join("bin");
+    std::fs::create_dir(&bin).unwrap();
+    let age = bin.join("age");
+    std::fs::write(
+        &age,
+        "#!/bin/sh\n[ \"$1\" = -d ] && [ \"$2\" = -i ] && [ -f \"$3\" ] && exec cat \"$4\"\nexit 1\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&age, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .arg("input-res")
+        .env("PATH", &path)
+        .env("HOME", &home)
+        .env("MICI_TEST_TOKEN", "from-env")
+        .current_dir(tmp.path())
+        .arg("secret-sources")
         .assert()
         .success()
-        .stdout(predicate::str::contains("Hello, World!"));
-}
+        .stdout(predicate::str::contains("file: ok"))
+        .stdout(predicate::str::contains("home: ok"))
+        .stdout(predicate::str::contains("env: ok"))
+        .stdout(predicate::str::contains("command: ok"))
+        .stdout(predicate::str::contains("vault: ok"))
+        .stdout(predicate::str::contains("printed: ***"));
 
-#[cfg(windows)]
-#[test]
-fn run_input_resolution_defaults() {
-    let tmp = setup_mici_home(&[(
-        "input-res.yml",
-        &fixture("valid_input_resolution_windows.yml"),
-    )]);
+    // A missing value fails, unless given on the command line
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .env("PATH", &path)
+        .env("HOME", &home)
+        .env_remove("MICI_TEST_TOKEN")
+        .current_dir(tmp.path())
+        .arg("secret-sources")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("source_failed"))
+        .stderr(predicate::str::contains("it isn't set"));
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .arg("input-res")
+        .env("PATH", &path)
+        .env("HOME", &home)
+        .env_remove("MICI_TEST_TOKEN")
+        .current_dir(tmp.path())
+        .args(["secret-sources", "--env_token", "from-env"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Hello, World!"));
+        .stdout(predicate::str::contains("env: ok"));
 }
 
 #[test]
-fn run_choice_input_with_value() {
-    let tmp = setup_mici_home(&[("choice.yml", &fixture("valid_choice_input.yml"))]);
+fn run_records_run() {
+    let tmp = setup_mici_home(&[("run-record.yml", &fixture("valid_run_record.yml"))]);
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .args(["choice", "--env", "staging"])
+        .args(["run-record", "--name", "world", "--token", "s3cr3t"])
         .assert()
-        .success()
-        .stdout(predicate::str::contains("deploying to staging"));
-}
+        .failure()
+        .code(3);
 
-#[test]
-fn run_choice_input_default() {
-    let tmp = setup_mici_home(&[("choice.yml", &fixture("valid_choice_input.yml"))]);
+    let runs: Vec<_> = std::fs::read_dir(tmp.path().join(".mici/runs/run-record"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(runs.len(), 1);
+    let run = &runs[0];
+    let read = |name: &str| std::fs::read_to_string(run.join(name)).unwrap();
+
+    let summary = read("run.yml");
+    assert!(summary.contains("command: run-record"));
+    assert!(summary.contains("status: failure"));
+    assert!(summary.contains("exit_code: 3"));
+    assert!(summary.contains("id: greet"));
+    assert!(summary.contains("started_at: 20"));
+
+    let command = read("command.yml");
+    assert!(command.contains("name: run-record"), "{}", command);
+    assert!(command.contains("hello world"), "{}", command);
+    assert!(command.contains("token ***"), "{}", command);
+    assert!(!command.contains("s3cr3t"), "{}", command);
+    assert!(!command.contains("null"), "{}", command);
+    assert!(read("inputs.yml").contains("name: world"));
+    assert!(read("inputs.yml").contains("token: '***'"));
+    assert!(!read("inputs.yml").contains("s3cr3t"));
+    assert_eq!(read("steps/greet.stdout.log"), "hello world\n");
+    assert_eq!(read("steps/greet.stderr.log"), "token ***\n");
+}
+
+#[test]
+fn history_lists_runs() {
+    let tmp = setup_mici_home(&[
+        ("run-record.yml", &fixture("valid_run_record.yml")),
+        ("hello.yml", &fixture("minimal_command.yml")),
+    ]);
+
+    for args in [&["run-record"][..], &["hello"][..]] {
+        let _ = mici().env("MICI_HOME", tmp.path()).args(args).assert();
+    }
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .arg("choice")
+        .arg("history")
         .assert()
         .success()
-        .stdout(predicate::str::contains("deploying to production"));
-}
+        .stdout(predicate::str::contains("hello"))
+        .stdout(predicate::str::contains("run-record"))
+        .stdout(predicate::str::contains("failure"))
+        .stdout(predicate::str::contains("success"));
 
-#[test]
-fn run_bool_input_present() {
-    let tmp = setup_mici_home(&[("booltest.yml", &fixture("valid_bool_input.yml"))]);
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["history", "--status", "failure"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("run-record"))
+        .stdout(predicate::str::contains("hello").not());
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .args(["booltest", "--dry-run"])
+        .args(["history", "hello"])
         .assert()
         .success()
-        .stdout(predicate::str::is_match("(?i)dry_run=true").unwrap());
+        .stdout(predicate::str::contains("hello"))
+        .stdout(predicate::str::contains("run-record").not());
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["history", "--status", "unknown"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown status 'unknown'"));
 }
 
 #[test]
-fn run_bool_input_absent() {
-    let tmp = setup_mici_home(&[("booltest.yml", &fixture("valid_bool_input.yml"))]);
+fn logs_prints_step_output() {
+    let tmp = setup_mici_home(&[("run-record.yml", &fixture("valid_run_record.yml"))]);
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .arg("booltest")
+        .args(["run-record", "--name", "world", "--token", "s3cr3t"])
+        .assert()
+        .code(3);
+
+    let run_id = std::fs::read_dir(tmp.path().join(".mici/runs/run-record"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .file_name()
+        .into_string()
+        .unwrap();
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["logs", &run_id])
         .assert()
         .success()
-        .stdout(predicate::str::is_match("(?i)dry_run=false").unwrap());
+        .stdout(predicate::str::contains("greet"))
+        .stdout(predicate::str::contains("hello world"))
+        .stderr(predicate::str::contains("token ***"));
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["logs", &run_id, "--step", "greet", "--follow"])
+        .assert()
+        .success()
+        .stdout("hello world\n");
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["logs", "missing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No run with id 'missing'"));
 }
 
 #[test]
-fn run_no_inputs_command() {
-    let tmp = setup_mici_home(&[("no-inputs.yml", &fixture("valid_no_inputs.yml"))]);
+#[cfg(unix)]
+fn history_and_logs_end_abandoned_runs() {
+    let tmp = setup_mici_home(&[]);
+
+    // A run whose mici process was killed before it could record the outcome
+    let mut child = std::process::Command::new("true").spawn().unwrap();
+    let pid = child.id();
+    child.wait().unwrap();
+
+    let run_id = format!("20261018-041231-{}", pid);
+    let run = tmp.path().join(".mici/runs/abandoned").join(&run_id);
+    std::fs::create_dir_all(run.join("steps")).unwrap();
+    std::fs::write(run.join("steps/build.stdout.log"), "building\n").unwrap();
+    std::fs::write(
+        run.join("run.yml"),
+        format!(
+            "id: {}\ncommand: abandoned\nuser: null\npid: {}\nstatus: running\n\
+             started_at: 2026-10-18T04:12:31Z\nfinished_at: null\nduration_ms: null\n\
+             exit_code: null\n",
+            run_id, pid
+        ),
+    )
+    .unwrap();
 
     mici()
         .env("MICI_HOME", tmp.path())
-        .arg("no-inputs")
+        .args(["history", "--status", "interrupted"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("no inputs needed"));
+        .stdout(predicate::str::contains("abandoned"));
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .args(["logs", &run_id, "--follow"])
+        .timeout(std::time::Duration::from_secs(5))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("building"))
+        .stdout(predicate::str::contains("interrupted"));
 }
 
 // ─── Run: input validation ───
@@ -610,6 +1899,61 @@ fn run_step_failure_forwards_exit_code() {
         ));
 }
 
+#[cfg(unix)]
+#[test]
+fn run_step_streams_output_before_failure() {
+    let tmp = setup_mici_home(&[(
+        "stream-output.yml",
+        &fixture("valid_step_stream_output.yml"),
+    )]);
+
+    use std::io::{BufRead, BufReader, Read};
+
+    // Both streams go to the same pipe, to see the order they're written out in
+    let mut child = std::process::Command::new("sh")
+        .args(["-c", "exec \"$0\" stream-output 2>&1"])
+        .arg(env!("CARGO_BIN_EXE_mici"))
+        .env("MICI_HOME", tmp.path())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut output = BufReader::new(child.stdout.take().unwrap());
+
+    // The step waits for `continue` after its first line, which has to arrive first
+    let mut line = String::new();
+    while !line.contains("out-1") {
+        line.clear();
+        assert_ne!(output.read_line(&mut line).unwrap(), 0, "no output");
+    }
+    std::fs::write(tmp.path().join("continue"), "").unwrap();
+
+    // A partial line shows up before the rest of it, which waits for `finish`
+    let mut partial = String::new();
+    while !partial.contains("progress") {
+        let buffer = output.fill_buf().unwrap();
+        assert!(!buffer.is_empty(), "no partial line");
+        partial.push_str(&String::from_utf8_lossy(buffer));
+        let length = buffer.len();
+        output.consume(length);
+    }
+    assert!(
+        !partial.contains("done"),
+        "partial line waited for its newline"
+    );
+    std::fs::write(tmp.path().join("finish"), "").unwrap();
+
+    let mut rest = String::new();
+    output.read_to_string(&mut rest).unwrap();
+    assert_eq!(child.wait().unwrap().code(), Some(3));
+
+    let done = rest
+        .find(" done")
+        .expect("rest of the partial line is missing");
+    let err = rest.find("err-1").expect("stderr is missing");
+    let out = rest.find("out-2").expect("stdout after stderr is missing");
+    assert!(done < err && err < out, "output is out of order: {}", rest);
+}
+
 #[test]
 fn run_nonexistent_command() {
     let tmp = setup_mici_home(&[]);
@@ -1049,6 +2393,78 @@ fn run_with_multiple_invalid_step_working_directories_reports_all() {
         .stderr(predicate::str::contains("@{inputs.other-dir}"));
 }
 
+// ─── Run: parallel steps ───
+
+#[cfg(unix)]
+#[test]
+fn run_parallel_steps_concurrently() {
+    let tmp = setup_mici_home(&[("parallel.yml", &fixture("valid_parallel_steps.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("parallel")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[waiter]"))
+        .stdout(predicate::str::contains("saw-marker"))
+        .stdout(predicate::str::contains("after-group"));
+}
+
+#[cfg(unix)]
+#[test]
+fn run_parallel_steps_fail_fast() {
+    let tmp = setup_mici_home(&[("fail-fast.yml", &fixture("valid_parallel_fail_fast.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("fail-fast")
+        .timeout(std::time::Duration::from_secs(4))
+        .assert()
+        .failure()
+        .code(4)
+        .stdout(predicate::str::contains("slow-finished").not())
+        .stdout(predicate::str::contains("after-group").not())
+        .stderr(predicate::str::contains("Parallel group failed"))
+        .stderr(predicate::str::contains("slow (cancelled)"));
+}
+
+#[cfg(unix)]
+#[test]
+fn run_parallel_steps_wait_for_all() {
+    let tmp = setup_mici_home(&[(
+        "wait-for-all.yml",
+        &fixture("valid_parallel_wait_for_all.yml"),
+    )]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("wait-for-all")
+        .assert()
+        .failure()
+        .code(4)
+        .stdout(predicate::str::contains("slow-finished"))
+        .stdout(predicate::str::contains("after-group").not());
+}
+
+#[cfg(unix)]
+#[test]
+fn run_step_depends_on() {
+    let tmp = setup_mici_home(&[("depends-on.yml", &fixture("valid_step_depends_on.yml"))]);
+
+    mici()
+        .env("MICI_HOME", tmp.path())
+        .arg("depends-on")
+        .assert()
+        .failure()
+        .code(5)
+        .stdout(predicate::str::contains("saw-marker"))
+        .stdout(predicate::str::contains("report-after-build"))
+        .stdout(predicate::str::contains("should-not-run").not())
+        .stderr(predicate::str::contains(
+            "dependency 'broken' did not succeed",
+        ));
+}
+
 // ─── Dynamic command help ───
 
 #[test]